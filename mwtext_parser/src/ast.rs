@@ -1,4 +1,7 @@
-use std::ops::Deref;
+use std::{
+    fmt::{self, Display},
+    ops::Deref,
+};
 
 use crate::lexer::Token;
 
@@ -114,3 +117,223 @@ pub struct HTMLClass<'a> {
     pub attributes: Vec<(&'a str, &'a str)>,
     pub text: Vec<Token<'a>>,
 }
+
+/// Inline markup, rendered as wikitext with [Display]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inline {
+    Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Underline(Vec<Inline>),
+    Strike(Vec<Inline>),
+    /// Hidden until selected, rendered as a `spoiler` span
+    Spoiler(Vec<Inline>),
+    /// Monospaced text that is not parsed as wikitext
+    Code(String),
+    ExternalLink {
+        url: String,
+        text: Vec<Inline>,
+    },
+    LineBreak,
+}
+
+impl Display for Inline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inline::Text(text) => write!(f, "{}", escape_text(text)),
+            Inline::Bold(inner) => write!(f, "'''{}'''", Inlines(inner)),
+            Inline::Italic(inner) => write!(f, "''{}''", Inlines(inner)),
+            Inline::Underline(inner) => write!(f, "<u>{}</u>", Inlines(inner)),
+            Inline::Strike(inner) => write!(f, "<s>{}</s>", Inlines(inner)),
+            Inline::Spoiler(inner) => {
+                write!(f, "<span class=\"spoiler\">{}</span>", Inlines(inner))
+            }
+            Inline::Code(code) => write!(
+                f,
+                "<code><nowiki>{}</nowiki></code>",
+                escape_closing(code, "nowiki")
+            ),
+            Inline::ExternalLink { url, text } => write!(f, "[{url} {}]", Inlines(text)),
+            Inline::LineBreak => writeln!(f, "<br />"),
+        }
+    }
+}
+
+/// Protocols MediaWiki turns `[url text]` into an external link for
+const URL_PROTOCOLS: [&str; 4] = ["http://", "https://", "//", "mailto:"];
+
+/// Keeps plain text from being expanded by MediaWiki: signatures (`~~~~`), templates,
+/// links, bold and italic quotes, behavior switches like `__TOC__` and HTML tags
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '~' | '\'' | '_' | '[' | ']' | '{' | '}' => {
+                rest.len() - rest.trim_start_matches(c).len()
+            }
+            _ => c.len_utf8(),
+        };
+        let (run, tail) = rest.split_at(len);
+        let markup = match c {
+            '~' => len >= 3,
+            '[' => {
+                len >= 2
+                    || URL_PROTOCOLS
+                        .iter()
+                        .any(|p| starts_with_ignore_case(tail, p))
+            }
+            '\'' | '_' | ']' | '{' | '}' => len >= 2,
+            _ => false,
+        };
+        if c == '<' {
+            escaped.push_str("&lt;");
+        } else if markup {
+            escaped.push_str(&format!("<nowiki>{run}</nowiki>"));
+        } else {
+            escaped.push_str(run);
+        }
+        rest = tail;
+    }
+    escaped
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.as_bytes()
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix.as_bytes()))
+}
+
+/// Escapes every `</tag` in `text`, so it can not close the `tag` element it is wrapped in
+fn escape_closing(text: &str, tag: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut escaped = String::with_capacity(text.len());
+    let mut last = 0;
+    for (pos, _) in lower.match_indices(&format!("</{tag}")) {
+        escaped.push_str(&text[last..pos]);
+        escaped.push_str("&lt;");
+        last = pos + 1;
+    }
+    escaped.push_str(&text[last..]);
+    escaped
+}
+
+/// Displays a run of [Inline] nodes one after the other
+pub struct Inlines<'a>(pub &'a [Inline]);
+
+impl Display for Inlines<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|node| write!(f, "{node}"))
+    }
+}
+
+/// Block level markup, rendered as wikitext with [Display]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Heading(Heading),
+    Paragraph(Vec<Inline>),
+    /// Items with their nesting depth, starting at 1
+    List {
+        ordered: bool,
+        items: Vec<(usize, Vec<Inline>)>,
+    },
+    Quote(Vec<Inline>),
+    Preformatted(String),
+}
+
+impl Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Block::Heading(heading) => write!(f, "{}", heading.to_string()),
+            Block::Paragraph(inner) => write!(f, "{}", escape_line_starts(inner)),
+            Block::List { ordered, items } => {
+                let marker = if *ordered { "#" } else { "*" };
+                for (i, (depth, item)) in items.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{} {}", marker.repeat(*depth), Inlines(item))?;
+                }
+                Ok(())
+            }
+            Block::Quote(inner) => {
+                write!(f, "<blockquote>{}</blockquote>", escape_line_starts(inner))
+            }
+            Block::Preformatted(text) => {
+                write!(f, "<pre>{}</pre>", escape_closing(text, "pre"))
+            }
+        }
+    }
+}
+
+/// Renders `inner`, keeping lines from starting a list, heading, indent, table or rule
+fn escape_line_starts(inner: &[Inline]) -> String {
+    Inlines(inner)
+        .to_string()
+        .split('\n')
+        .map(|line| {
+            if line.starts_with(['*', '#', ':', ';', '=', ' ', '{', '|', '-']) {
+                format!("<nowiki/>{line}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Blocks separated by blank lines
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Document(pub Vec<Block>);
+
+impl Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, block) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "\n\n")?;
+            }
+            write!(f, "{block}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_text() {
+        let test_cases = vec![
+            ("Signed ~~~~", "Signed <nowiki>~~~~</nowiki>"),
+            ("~ and ~~", "~ and ~~"),
+            ("{{Delete}}", "<nowiki>{{</nowiki>Delete<nowiki>}}</nowiki>"),
+            (
+                "[[Main Page]]",
+                "<nowiki>[[</nowiki>Main Page<nowiki>]]</nowiki>",
+            ),
+            (
+                "[https://example.com]",
+                "<nowiki>[</nowiki>https://example.com]",
+            ),
+            ("[1] and [a]", "[1] and [a]"),
+            ("__TOC__", "<nowiki>__</nowiki>TOC<nowiki>__</nowiki>"),
+            ("it''s", "it<nowiki>''</nowiki>s"),
+            ("don't", "don't"),
+            ("<b>raw</b>", "&lt;b>raw&lt;/b>"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(Inline::Text(input.to_string()).to_string(), expected);
+        }
+    }
+    #[test]
+    fn test_escape_code() {
+        let code = Inline::Code("a</nowiki>''b''</NOWIKI >".to_string());
+        assert_eq!(
+            code.to_string(),
+            "<code><nowiki>a&lt;/nowiki>''b''&lt;/NOWIKI ></nowiki></code>"
+        );
+        let pre = Block::Preformatted("</pre>".to_string());
+        assert_eq!(pre.to_string(), "<pre>&lt;/pre></pre>");
+    }
+}
@@ -1,9 +1,17 @@
-use std::fs;
+use std::{fs, path::PathBuf};
 
+use chrono::NaiveDate;
 use clap::Parser;
 use sd_archivemanager::{
-    converters::{eo::handle_eo_id, legislation::handle_law_id},
-    guilds::Guilds,
+    converters::{
+        Destination, PublishOutcome, UploadMode, UploadOptions,
+        case_law::{handle_case, handle_case_rerender},
+        eo::{handle_eo, handle_eo_rerender, handle_eo_sync},
+        index::rebuild,
+        legislation::{handle_law, handle_law_rerender, handle_law_sync},
+    },
+    guilds::{GuildInfo, Guilds},
+    regex::Target,
 };
 use xdg::BaseDirectories;
 
@@ -18,6 +26,42 @@ struct Args {
     #[clap(short, long)]
     guild: String,
 
+    /// Render pages without logging in or editing the wiki
+    #[clap(long, global = true)]
+    dry_run: bool,
+
+    /// Write dry-run output to this file instead of stdout
+    #[clap(short, long, global = true, requires = "dry_run")]
+    output: Option<PathBuf>,
+
+    /// Overwrite existing pages without showing the diff and asking first
+    #[clap(short, long, global = true)]
+    yes: bool,
+
+    /// Edit summary template, overrides the one configured for the target
+    #[clap(long, global = true)]
+    summary: Option<String>,
+
+    /// Mark edits as minor
+    #[clap(long, global = true)]
+    minor: bool,
+
+    /// Add a change tag to every edit, can be repeated
+    #[clap(long = "tag", global = true)]
+    tags: Vec<String>,
+
+    /// Add a category on top of the guild defaults, can be repeated
+    #[clap(short, long, global = true)]
+    categories: Vec<String>,
+
+    /// Only create new pages, fail on pages that already exist
+    #[clap(long, global = true, conflicts_with = "update_only")]
+    create_only: bool,
+
+    /// Only update existing pages, fail on pages that do not exist yet
+    #[clap(long, global = true)]
+    update_only: bool,
+
     /// Subcommand to execute
     #[clap(subcommand)]
     command: Command,
@@ -26,7 +70,41 @@ struct Args {
 #[derive(Debug, clap::Subcommand)]
 enum LawCommand {
     /// Upload a law document
-    Upload { id: u64 },
+    Upload {
+        /// Link to the message posting the law, or its ID
+        link: String,
+    },
+    /// Archive every law document linked in the guild's legislation channel
+    Sync {
+        /// Only archive laws posted on or after this date (YYYY-MM-DD)
+        #[clap(long)]
+        since: Option<NaiveDate>,
+        /// Only archive laws posted on or before this date (YYYY-MM-DD)
+        #[clap(long)]
+        until: Option<NaiveDate>,
+        /// Archive at most this many laws, newest first
+        #[clap(long)]
+        limit: Option<usize>,
+    },
+    /// Render archived laws again from the local store, without scraping
+    Rerender {
+        /// Message ID of the law to re-render, every archived law if omitted
+        id: Option<u64>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum CaseCommand {
+    /// Upload a court verdict
+    Upload {
+        /// Link to the message posting the verdict, or its ID
+        link: String,
+    },
+    /// Render archived verdicts again from the local store, without scraping
+    Rerender {
+        /// Message ID of the verdict to re-render, every archived verdict if omitted
+        id: Option<u64>,
+    },
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -40,58 +118,198 @@ enum Command {
     Law {
         #[clap(subcommand)]
         subcommand: LawCommand,
-        #[clap(short, long)]
-        /// Specify the categories to upload to
-        categories: Vec<String>,
+    },
+    /// Case law-related commands
+    Case {
+        #[clap(subcommand)]
+        subcommand: CaseCommand,
+    },
+    /// Index page-related commands
+    Index {
+        #[clap(subcommand)]
+        subcommand: IndexCommand,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum IndexCommand {
+    /// Regenerate the index pages from the local store
+    Rebuild {
+        /// Only rebuild the index of this target (eo, legislation or caselaw)
+        #[clap(value_parser = parse_target)]
+        target: Option<Target>,
     },
 }
 #[derive(Debug, clap::Subcommand)]
 enum EOCommand {
     /// Upload a link or ID
-    Upload { id: u64 },
+    Upload {
+        /// Link to the EO thread or a message in it, or the thread ID
+        link: String,
+    },
+    /// Archive every EO in the guild's EO forum
+    Sync {
+        /// Only archive EOs created on or after this date (YYYY-MM-DD)
+        #[clap(long)]
+        since: Option<NaiveDate>,
+    },
+    /// Render archived EOs again from the local store, without scraping
+    Rerender {
+        /// Message ID of the EO to re-render, every archived EO if omitted
+        id: Option<u64>,
+    },
 }
 
+/// Template used when no template file is configured, the content followed by its attachments
+const DEFAULT_TEMPLATE: &str =
+    "{content}\n{{ for file in attachments }}\n{file.embed}{{ endfor }}";
+
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
     let xdg = BaseDirectories::with_prefix("sd-archivemanager").unwrap();
-    match args.command {
-        Command::EO { subcommand } => match subcommand {
-            EOCommand::Upload { id } => {
-                handle_eo_id(
-                    fs::read_to_string(xdg.place_config_file("eo_template").unwrap())
-                        .unwrap()
-                        .as_str(),
-                    id,
-                )
-                .await
-                .unwrap();
+    let opts = UploadOptions {
+        destination: match (args.dry_run, args.output) {
+            (false, _) => Destination::Wiki,
+            (true, None) => Destination::Stdout,
+            (true, Some(path)) => {
+                // pages are appended, start with an empty file
+                fs::write(&path, "").unwrap();
+                Destination::File(path)
             }
         },
-        Command::Law {
-            subcommand,
-            categories,
-        } => {
-            let guildman = Guilds::load().unwrap_or_default();
-            let guild = guildman
-                .get_guilds()
-                .iter()
-                .find(|g| g.name == args.guild)
-                .unwrap_or_else(|| panic!("Guild {} not found", args.guild));
+        confirm: !args.yes,
+        summary: args.summary,
+        minor: args.minor,
+        tags: args.tags,
+        mode: match (args.create_only, args.update_only) {
+            (true, _) => UploadMode::CreateOnly,
+            (_, true) => UploadMode::UpdateOnly,
+            _ => UploadMode::Overwrite,
+        },
+        categories: args.categories,
+        ..Default::default()
+    };
+    match args.command {
+        Command::EO { subcommand } => {
+            let guild = find_guild(&args.guild);
+            let template =
+                fs::read_to_string(xdg.place_config_file("eo_template").unwrap()).unwrap();
             match subcommand {
-                LawCommand::Upload { id } => {
-                    let template = format!(
-                        "{}\n{}",
-                        categories
-                            .iter()
-                            .map(|x| format!("[[Category:{}]] ", x))
-                            .collect::<String>(),
-                        fs::read_to_string(xdg.place_config_file("law_template").unwrap())
-                            .unwrap_or(format!("{{content}}"))
-                    );
-                    handle_law_id(&template, id, guild).await.unwrap()
+                EOCommand::Upload { link } => {
+                    report(handle_eo(&link, &template, &guild, &opts).await.unwrap());
+                }
+                EOCommand::Sync { since } => {
+                    let failed = handle_eo_sync(&template, &guild, since, &opts)
+                        .await
+                        .unwrap();
+                    for (id, err) in failed {
+                        eprintln!("Failed to archive EO {id}: {err}");
+                    }
+                }
+                EOCommand::Rerender { id } => {
+                    let failed = handle_eo_rerender(&template, &guild, id, &opts)
+                        .await
+                        .unwrap();
+                    for (id, err) in failed {
+                        eprintln!("Failed to render EO {id}: {err}");
+                    }
+                }
+            }
+        }
+        Command::Law { subcommand } => {
+            let guild = find_guild(&args.guild);
+            let template = fs::read_to_string(xdg.place_config_file("law_template").unwrap())
+                .unwrap_or(DEFAULT_TEMPLATE.to_string());
+            match subcommand {
+                LawCommand::Upload { link } => {
+                    report(handle_law(&link, &template, &guild, &opts).await.unwrap());
+                }
+                LawCommand::Sync {
+                    since,
+                    until,
+                    limit,
+                } => {
+                    let failed = handle_law_sync(&template, &guild, since, until, limit, &opts)
+                        .await
+                        .unwrap();
+                    for (id, err) in failed {
+                        eprintln!("Failed to archive law {id}: {err}");
+                    }
+                }
+                LawCommand::Rerender { id } => {
+                    let failed = handle_law_rerender(&template, &guild, id, &opts)
+                        .await
+                        .unwrap();
+                    for (id, err) in failed {
+                        eprintln!("Failed to render law {id}: {err}");
+                    }
                 }
             };
         }
+        Command::Case { subcommand } => {
+            let guild = find_guild(&args.guild);
+            let template = fs::read_to_string(xdg.place_config_file("case_template").unwrap())
+                .unwrap_or(DEFAULT_TEMPLATE.to_string());
+            match subcommand {
+                CaseCommand::Upload { link } => {
+                    report(handle_case(&link, &template, &guild, &opts).await.unwrap());
+                }
+                CaseCommand::Rerender { id } => {
+                    let failed = handle_case_rerender(&template, &guild, id, &opts)
+                        .await
+                        .unwrap();
+                    for (id, err) in failed {
+                        eprintln!("Failed to render verdict {id}: {err}");
+                    }
+                }
+            }
+        }
+        Command::Index { subcommand } => {
+            let guild = find_guild(&args.guild);
+            match subcommand {
+                IndexCommand::Rebuild { target } => {
+                    let targets = match target {
+                        Some(target) => vec![target],
+                        None => vec![Target::EO, Target::Legislation, Target::CaseLaw],
+                    };
+                    for target in targets {
+                        match rebuild(&guild, target, &opts).await.unwrap() {
+                            Some(outcome) => report(outcome),
+                            None => eprintln!("No {} index page configured", target.name()),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn parse_target(name: &str) -> Result<Target, String> {
+    [Target::EO, Target::Legislation, Target::CaseLaw]
+        .into_iter()
+        .find(|t| t.name() == name)
+        .ok_or_else(|| format!("unknown target {name}"))
+}
+
+fn find_guild(name: &str) -> GuildInfo {
+    let guildman = Guilds::load().unwrap_or_default();
+    guildman
+        .get_guilds()
+        .iter()
+        .find(|g| g.name == name)
+        .unwrap_or_else(|| panic!("Guild {} not found", name))
+        .clone()
+}
+
+fn report(outcome: PublishOutcome) {
+    if let PublishOutcome::Edited(edit) = outcome {
+        match edit.newrevid {
+            Some(revid) => println!(
+                "Archived {} (page {}, revision {})",
+                edit.title, edit.pageid, revid
+            ),
+            None => println!("{} did not change", edit.title),
+        }
     }
 }
@@ -1,8 +1,12 @@
-use crate::error::*;
+use crate::{
+    error::*,
+    regex::{PerTarget, Target},
+};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 use snafu::ResultExt;
 use std::{
+    collections::BTreeMap,
     env,
     fs::OpenOptions,
     io::{self, Write},
@@ -15,6 +19,137 @@ pub struct Config {
     pub passwd: String,
     pub endpoint: String,
     pub bot: bool,
+    #[serde(default)]
+    pub edit: EditSettings,
+    #[serde(default)]
+    pub auth: WikiAuth,
+    #[serde(default)]
+    pub category: CategorySettings,
+    #[serde(default)]
+    pub retry: RetrySettings,
+    #[serde(default)]
+    pub eo: EOSettings,
+    /// Wiki page per discord user ID or username, e.g. `f3rri5_ = "User:Ferris"`,
+    /// linked from the author of their documents
+    #[serde(default)]
+    pub wiki_users: BTreeMap<String, String>,
+}
+
+/// How the messages of an EO thread are turned into one document
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct EOSettings {
+    /// Append the messages the author posted right after the starter message to the content
+    pub merge: bool,
+    /// Longest gap in minutes between two messages that still continue each other, 0 for no limit
+    pub merge_window: u64,
+    /// Put between merged messages
+    pub separator: String,
+    /// Add messages the author posted later in the thread as a section of their own
+    pub amendments: bool,
+    /// Heading of the amendments section
+    pub amendments_heading: String,
+}
+
+impl Default for EOSettings {
+    fn default() -> Self {
+        EOSettings {
+            merge: true,
+            merge_window: 30,
+            separator: "\n\n".to_string(),
+            amendments: false,
+            amendments_heading: "Amendments".to_string(),
+        }
+    }
+}
+
+/// How wiki requests are retried when the wiki is lagged, rate limited or unavailable
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RetrySettings {
+    /// Retries after the first attempt, 0 disables retrying
+    pub max_retries: u32,
+    /// Delay before the first retry in milliseconds, doubled for every further retry
+    pub base_delay_ms: u64,
+    /// Upper bound of the doubled delay in milliseconds, `Retry-After` is honoured regardless
+    pub max_delay_ms: u64,
+    /// `maxlag` sent with every request, requests are retried while the replication lag
+    /// of the wiki's database is higher than this many seconds
+    pub maxlag: Option<u32>,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetrySettings {
+            max_retries: 5,
+            base_delay_ms: 1000,
+            max_delay_ms: 60000,
+            maxlag: Some(5),
+        }
+    }
+}
+
+/// How category pages are handled
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CategorySettings {
+    /// Create category pages that do not exist yet after a page was archived
+    pub create: bool,
+    /// Text of created category pages, `{category}` is replaced with the category name
+    pub description: String,
+}
+
+impl Default for CategorySettings {
+    fn default() -> Self {
+        CategorySettings {
+            create: true,
+            description: "Documents in {category}, archived from Discord.".to_string(),
+        }
+    }
+}
+
+/// How the wiki session is authenticated
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum WikiAuth {
+    /// `action=clientlogin` with the account's own `login` and `passwd`
+    #[default]
+    ClientLogin,
+    /// `action=login` with a Special:BotPasswords grant, `login` is `User@BotName`
+    /// and `passwd` the generated bot password
+    BotPassword,
+    /// OAuth 2.0 owner-only consumer, the access token is sent as a Bearer header
+    /// and `login`/`passwd` are not used
+    OAuth { access_token: String },
+}
+
+/// Defaults for the edits made on the wiki
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct EditSettings {
+    /// Mark edits as bot edits, requires the `bot` right on the wiki
+    pub bot: bool,
+    pub minor: bool,
+    /// Change tags added to every edit, the tags have to be defined on the wiki
+    pub tags: Vec<String>,
+    /// Summary templates per target, rendered with the formatted document in the same syntax
+    /// as page templates, e.g. `Archive EO {name} by {author}`. See [EditSettings::summary].
+    #[serde(skip_serializing_if = "PerTarget::is_empty")]
+    pub summary: PerTarget<Option<String>>,
+}
+
+impl EditSettings {
+    /// Summary template for edits to `target` pages, the configured one or a default
+    pub fn summary(&self, target: Target) -> &str {
+        self.summary
+            .get(target)
+            .as_deref()
+            .unwrap_or(match target {
+                Target::EO => "Archive EO {name} by {author}",
+                Target::Legislation => "Archive {name} by {author}",
+                Target::CaseLaw => "Archive verdict {name}",
+            })
+    }
 }
 
 impl Config {
@@ -24,6 +159,15 @@ impl Config {
     pub fn get_token(&self) -> &String {
         &self.token
     }
+    /// Wiki user the edits are made as, [None] if only the OAuth token is known
+    pub fn wiki_user(&self) -> Option<&str> {
+        match &self.auth {
+            WikiAuth::ClientLogin => Some(&self.login),
+            // the part after @ only names the grant
+            WikiAuth::BotPassword => self.login.split('@').next(),
+            WikiAuth::OAuth { .. } => None,
+        }
+    }
     pub fn save(&self) -> Result<(), Error> {
         let xdg_dirs = xdg::BaseDirectories::with_prefix("sd-archivemanager").unwrap();
         let conf_file =
@@ -91,6 +235,12 @@ impl Default for Config {
             login: env::var("LOGIN").unwrap_or("TO BE FILLED".to_string()),
             endpoint: "https://qwrky.dev/mediawiki/api.php".to_string(),
             bot: true,
+            edit: EditSettings::default(),
+            auth: WikiAuth::default(),
+            category: CategorySettings::default(),
+            retry: RetrySettings::default(),
+            eo: EOSettings::default(),
+            wiki_users: BTreeMap::new(),
         };
         conf_file
             .write_all(
@@ -111,51 +261,76 @@ impl Default for Config {
     }
 }
 
+#[cfg(test)]
+impl Config {
+    /// Empty credentials and default settings, without touching the config file like
+    /// [Config::default] does
+    pub(crate) fn for_tests() -> Self {
+        Config {
+            token: String::new(),
+            login: String::new(),
+            passwd: String::new(),
+            endpoint: String::new(),
+            bot: true,
+            edit: EditSettings::default(),
+            auth: WikiAuth::default(),
+            category: CategorySettings::default(),
+            retry: RetrySettings::default(),
+            eo: EOSettings::default(),
+            wiki_users: BTreeMap::new(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
 
     use super::*;
+
+    /// What [Config::default] creates with the environment of the test
+    fn expected_default() -> Config {
+        Config {
+            token: env::var("DISCORD_TOKEN").unwrap_or("TO BE FILLED".to_string()),
+            passwd: env::var("PASSWD").unwrap_or("TO BE FILLED".to_string()),
+            login: env::var("LOGIN").unwrap_or("TO BE FILLED".to_string()),
+            endpoint: "https://qwrky.dev/mediawiki/api.php".to_string(),
+            ..Config::for_tests()
+        }
+    }
     #[test]
     fn create_default() {
         let config = super::Config::default();
-        assert_eq!(
-            config,
-            Config {
-                token: env::var("DISCORD_TOKEN").unwrap_or("TO BE FILLED".to_string()),
-                passwd: env::var("PASSWD").unwrap_or("TO BE FILLED".to_string()),
-                login: env::var("LOGIN").unwrap_or("TO BE FILLED".to_string()),
-                endpoint: "https://qwrky.dev/mediawiki/api.php".to_string(),
-                bot: true
-            }
-        );
+        assert_eq!(config, expected_default());
     }
     #[test]
     fn parse_config() {
         let config = super::Config::default();
         let config = toml::to_string(&config).unwrap();
         let config: super::Config = toml::from_str(&config).unwrap();
-        assert_eq!(
-            config,
-            Config {
-                token: env::var("DISCORD_TOKEN").unwrap_or("TO BE FILLED".to_string()),
-                passwd: env::var("PASSWD").unwrap_or("TO BE FILLED".to_string()),
-                login: env::var("LOGIN").unwrap_or("TO BE FILLED".to_string()),
-                endpoint: "https://qwrky.dev/mediawiki/api.php".to_string(),
-                bot: true
-            }
-        );
+        assert_eq!(config, expected_default());
         let config = Config::load().unwrap();
+        assert_eq!(config, expected_default());
+        fs::remove_file("/root/.config/sd-archivemanager/config.toml").unwrap();
+    }
+    #[test]
+    fn parse_summaries() {
+        let edit: EditSettings = toml::from_str("[summary]\neo = \"Add {name}\"").unwrap();
+        assert_eq!(edit.summary(Target::EO), "Add {name}");
+        assert_eq!(edit.summary(Target::Legislation), "Archive {name} by {author}");
+        assert!(EditSettings::default().summary.is_empty());
+    }
+    #[test]
+    fn parse_auth() {
+        let auth: WikiAuth = toml::from_str("method = \"botpassword\"").unwrap();
+        assert_eq!(auth, WikiAuth::BotPassword);
+        let auth: WikiAuth =
+            toml::from_str("method = \"oauth\"\naccess_token = \"abc\"").unwrap();
         assert_eq!(
-            config,
-            Config {
-                token: env::var("DISCORD_TOKEN").unwrap_or("TO BE FILLED".to_string()),
-                passwd: env::var("PASSWD").unwrap_or("TO BE FILLED".to_string()),
-                login: env::var("LOGIN").unwrap_or("TO BE FILLED".to_string()),
-                endpoint: "https://qwrky.dev/mediawiki/api.php".to_string(),
-                bot: true
+            auth,
+            WikiAuth::OAuth {
+                access_token: "abc".to_string()
             }
         );
-        fs::remove_file("/root/.config/sd-archivemanager/config.toml").unwrap();
     }
 }
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, discord, error::Error, mediawiki::MediaWikiClient};
+
+/// A file attached to a discord message, mirrored to the wiki as `File:<wiki_name>`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// Discord attachment ID
+    pub id: u64,
+    /// File name as uploaded to discord
+    pub filename: String,
+    pub content_type: Option<String>,
+    /// Size in bytes
+    pub size: u64,
+    /// Discord CDN URL the file is downloaded from
+    pub url: String,
+    /// Link to the message the file was attached to
+    pub source: String,
+    /// Name of the file on the wiki without the `File:` prefix, derived from the IDs
+    /// so the same attachment always maps to the same file
+    pub wiki_name: String,
+    /// Wikitext embedding images as thumbnails and linking other files
+    pub embed: String,
+}
+
+/// Converts the attachments of message `message` in `channel`.
+/// `guild` is only used for the link back to the message.
+pub(crate) fn collect(
+    attachments: Vec<discord::Attachment>,
+    guild: Option<u64>,
+    channel: u64,
+    message: u64,
+) -> Vec<Attachment> {
+    let source = format!(
+        "https://discord.com/channels/{}/{channel}/{message}",
+        guild.map_or("@me".to_string(), |g| g.to_string())
+    );
+    attachments
+        .into_iter()
+        .map(|a| {
+            let wiki_name = wiki_name(message, a.id, &a.filename);
+            let is_image = a
+                .content_type
+                .as_deref()
+                .is_some_and(|t| t.starts_with("image/"));
+            let embed = if is_image {
+                format!("[[File:{wiki_name}|thumb|{}]]", a.filename)
+            } else {
+                format!("[[Media:{wiki_name}|{}]]", a.filename)
+            };
+            Attachment {
+                id: a.id,
+                filename: a.filename,
+                content_type: a.content_type,
+                size: a.size,
+                url: a.url,
+                source: source.clone(),
+                wiki_name,
+                embed,
+            }
+        })
+        .collect()
+}
+
+/// `Discord <message>-<attachment> <filename>`, with characters MediaWiki does not allow
+/// in titles replaced
+fn wiki_name(message: u64, attachment: u64, filename: &str) -> String {
+    let filename = filename
+        .chars()
+        .map(|c| match c {
+            '#' | '<' | '>' | '[' | ']' | '|' | '{' | '}' | ':' | '/' | '\\' => '_',
+            c => c,
+        })
+        .collect::<String>();
+    format!("Discord {message}-{attachment} {filename}")
+}
+
+/// Uploads every attachment that is not on the wiki yet.
+/// Files are never overwritten, an existing `File:` page counts as mirrored.
+pub async fn mirror(
+    attachments: &[Attachment],
+    wiki: &mut MediaWikiClient,
+    cfg: &Config,
+) -> Result<(), Error> {
+    let discord = discord::shared(cfg);
+    for attachment in attachments {
+        let title = format!("File:{}", attachment.wiki_name);
+        if wiki.page(&title).await?.is_some() {
+            continue;
+        }
+        let file = discord.download(&attachment.url).await?;
+        wiki.upload_file(
+            &attachment.wiki_name,
+            file,
+            attachment.content_type.as_deref(),
+            &format!("Mirror {} from discord", attachment.filename),
+            &description(attachment),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Description page of a mirrored file
+fn description(attachment: &Attachment) -> String {
+    format!(
+        "== Summary ==\n{} attached to [{} a discord message].\n",
+        attachment.filename, attachment.source
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_attachments() {
+        let raw = serde_json::from_value::<Vec<discord::Attachment>>(serde_json::json!([
+            {"id": "7", "filename": "seal.png", "content_type": "image/png", "size": 10,
+                "url": "https://cdn.discordapp.com/attachments/2/7/seal.png"},
+            {"id": "8", "filename": "act: final.pdf", "size": 20,
+                "url": "https://cdn.discordapp.com/attachments/2/8/act.pdf"}
+        ]))
+        .unwrap();
+        let attachments = collect(raw, Some(1), 2, 3);
+        assert_eq!(attachments[0].wiki_name, "Discord 3-7 seal.png");
+        assert_eq!(attachments[0].embed, "[[File:Discord 3-7 seal.png|thumb|seal.png]]");
+        assert_eq!(attachments[0].source, "https://discord.com/channels/1/2/3");
+        assert_eq!(attachments[1].wiki_name, "Discord 3-8 act_ final.pdf");
+        assert_eq!(
+            attachments[1].embed,
+            "[[Media:Discord 3-8 act_ final.pdf|act: final.pdf]]"
+        );
+    }
+}
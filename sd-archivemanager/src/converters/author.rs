@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::discord::{self, DiscordClient};
+
+/// The discord user who posted a document. Flattened into the documents, so `author` stays
+/// the username for templates and plugins and the rest is added as `author_*` fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(from = "StoredAuthor")]
+pub struct Author {
+    /// Unique discord username, used to match regex profiles and plugins
+    #[serde(rename = "author")]
+    pub username: String,
+    /// Discord user ID, `0` for documents stored before IDs were recorded
+    #[serde(rename = "author_id")]
+    pub id: u64,
+    #[serde(rename = "author_global_name")]
+    pub global_name: Option<String>,
+    /// Nickname in the guild the document was posted in
+    #[serde(rename = "author_nick")]
+    pub nick: Option<String>,
+    /// Name discord shows: the nickname, the display name or the username
+    #[serde(rename = "author_display_name")]
+    pub display_name: String,
+    /// Wiki page of the user, from [Config::wiki_users](crate::config::Config::wiki_users)
+    #[serde(rename = "author_page")]
+    pub page: Option<String>,
+    /// `[[page|display_name]]` if the user has a page, the display name otherwise
+    #[serde(rename = "author_link")]
+    pub link: String,
+}
+
+/// The stored fields, older records only have the username.
+/// The display name and link are derived again.
+#[derive(Deserialize)]
+struct StoredAuthor {
+    #[serde(rename = "author")]
+    username: String,
+    #[serde(rename = "author_id", default)]
+    id: u64,
+    #[serde(rename = "author_global_name", default)]
+    global_name: Option<String>,
+    #[serde(rename = "author_nick", default)]
+    nick: Option<String>,
+    #[serde(rename = "author_page", default)]
+    page: Option<String>,
+}
+
+impl From<StoredAuthor> for Author {
+    fn from(stored: StoredAuthor) -> Self {
+        let mut author = Author::new(stored.id, stored.username, stored.global_name, stored.nick);
+        author.set_page(stored.page);
+        author
+    }
+}
+
+impl From<String> for Author {
+    fn from(username: String) -> Self {
+        Author::new(0, username, None, None)
+    }
+}
+
+impl Author {
+    pub fn new(
+        id: u64,
+        username: String,
+        global_name: Option<String>,
+        nick: Option<String>,
+    ) -> Self {
+        let display_name = nick
+            .clone()
+            .or(global_name.clone())
+            .unwrap_or(username.clone());
+        Author {
+            id,
+            username,
+            global_name,
+            nick,
+            link: display_name.clone(),
+            display_name,
+            page: None,
+        }
+    }
+
+    /// Looks the author up in `wiki_users`, by user ID first and username second
+    pub fn map_wiki_user(&mut self, wiki_users: &BTreeMap<String, String>) {
+        let page = wiki_users
+            .get(&self.id.to_string())
+            .or(wiki_users.get(&self.username))
+            .cloned();
+        self.set_page(page);
+    }
+
+    fn set_page(&mut self, page: Option<String>) {
+        self.link = match &page {
+            Some(page) => format!("[[{page}|{}]]", self.display_name),
+            None => self.display_name.clone(),
+        };
+        self.page = page;
+    }
+}
+
+/// The author of a message posted in `guild`, with the guild nickname if they are still a member
+pub(crate) async fn fetch(
+    user: discord::User,
+    guild: Option<u64>,
+    discord: &DiscordClient,
+) -> Author {
+    let nick = match guild {
+        Some(guild) => discord
+            .member(guild, user.id)
+            .await
+            .ok()
+            .and_then(|member| member.nick),
+        None => None,
+    };
+    Author::new(user.id, user.username, user.global_name, nick)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wiki_user_links() {
+        let mut author = Author::new(3, "f3rri5_".to_string(), Some("Ferris".to_string()), None);
+        assert_eq!(author.display_name, "Ferris");
+        let wiki_users = BTreeMap::from([("f3rri5_".to_string(), "User:Ferris".to_string())]);
+        author.map_wiki_user(&wiki_users);
+        assert_eq!(author.link, "[[User:Ferris|Ferris]]");
+        let wiki_users = BTreeMap::from([
+            ("3".to_string(), "Ferris the Crab".to_string()),
+            ("f3rri5_".to_string(), "User:Ferris".to_string()),
+        ]);
+        author.map_wiki_user(&wiki_users);
+        assert_eq!(author.page.as_deref(), Some("Ferris the Crab"));
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Doc {
+            #[serde(flatten)]
+            author: Author,
+        }
+        let stored: Doc = serde_json::from_str(r#"{"author": "f3rri5_"}"#).unwrap();
+        assert_eq!(stored.author, Author::from("f3rri5_".to_string()));
+        let doc = serde_json::to_value(Doc { author }).unwrap();
+        assert_eq!(doc["author"], "f3rri5_");
+        assert_eq!(doc["author_link"], "[[Ferris the Crab|Ferris]]");
+        let doc: Doc = serde_json::from_value(doc).unwrap();
+        assert_eq!(doc.author.page.as_deref(), Some("Ferris the Crab"));
+    }
+}
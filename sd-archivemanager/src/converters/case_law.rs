@@ -0,0 +1,284 @@
+use chrono::{NaiveDate, Utc};
+use regex::Regex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt, ResultExt, ensure_whatever};
+use std::sync::Arc;
+use tokio::sync::MutexGuard;
+use url::Url;
+
+use crate::{
+    CONFIG,
+    config::Config,
+    discord,
+    error::{Error, InvalidRegexSnafu},
+    guilds::GuildInfo,
+    mentions,
+    regex::Target,
+};
+
+use super::{
+    PageData, PublishOutcome, UploadOptions,
+    attachment::{self, Attachment},
+    author::{self, Author},
+    GDOC_LINK, archive, fetch_gdoc, rerender, templater,
+};
+
+/// Representation of a court verdict.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseLawInfo {
+    pub name: String,
+    /// Judge who posted the verdict
+    #[serde(flatten)]
+    pub author: Author,
+    pub case_number: String,
+    pub judges: Vec<String>,
+    pub parties: Vec<String>,
+    /// Date of the verdict
+    pub date: NaiveDate,
+    /// Opinion text
+    pub content: String,
+    /// Link to the opinion document, if it was posted as a Google Doc
+    pub link: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+/// Header fields found at the start of a verdict message, for example
+/// ```text
+/// Case No: 2025-CV-12
+/// Parties: Alice v. Bob
+/// Judges: Carol, Dave
+/// Date: 2025-03-10
+/// ```
+/// Lines after the header make up the opinion.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct VerdictHeader {
+    case_number: String,
+    judges: Vec<String>,
+    parties: Vec<String>,
+    date: Option<NaiveDate>,
+    body: String,
+}
+
+fn parse_verdict(content: &str) -> VerdictHeader {
+    let field = Regex::new(r"^\s*\**\s*([A-Za-z. ]+?)\s*:\s*\**\s*(.*?)\s*$").unwrap();
+    let parties_sep = Regex::new(r"(?i)\s+(?:v\.?|vs\.?)\s+").unwrap();
+    let judges_sep = Regex::new(r"\s*(?:,|\band\b|&)\s*").unwrap();
+    let mut header = VerdictHeader::default();
+    let mut lines = content.lines().peekable();
+    while let Some(line) = lines.peek() {
+        if line.trim().is_empty() {
+            lines.next();
+            continue;
+        }
+        let Some(caps) = field.captures(line) else {
+            break;
+        };
+        let value = caps[2].trim_end_matches('*').trim();
+        match caps[1].to_lowercase().trim_end_matches('.') {
+            "case" | "case no" | "case number" => header.case_number = value.to_string(),
+            "judge" | "judges" => {
+                header.judges = judges_sep
+                    .split(value)
+                    .filter(|x| !x.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "parties" => {
+                header.parties = parties_sep
+                    .split(value)
+                    .filter(|x| !x.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "date" | "verdict date" => {
+                header.date = ["%Y-%m-%d", "%d/%m/%Y", "%d %B %Y", "%B %d, %Y"]
+                    .iter()
+                    .find_map(|f| NaiveDate::parse_from_str(value, f).ok())
+            }
+            _ => break,
+        }
+        lines.next();
+    }
+    header.body = lines.collect::<Vec<_>>().join("\n");
+    header
+}
+
+impl PageData<'_> for CaseLawInfo {
+    /// Create a verdict object by scraping a message in [GuildInfo::case_src]
+    async fn scrape(
+        id: u64,
+        conf: Option<&MutexGuard<'_, Config>>,
+        client: Arc<Client>,
+        guild: Option<&GuildInfo>,
+    ) -> Result<Self, Error> {
+        let cfg;
+        let lock;
+        if conf.is_none() {
+            lock = CONFIG.lock().await;
+            cfg = &lock;
+        } else {
+            cfg = conf.unwrap();
+        }
+        let guild = guild.whatever_context("case law requires a guild")?;
+        let discord = discord::shared(cfg);
+        let mut message = discord.message(guild.case_src, id).await?;
+        message.content =
+            mentions::resolve(&message.content, Some(guild.id), &guild.mentions, discord).await;
+        let author = author::fetch(message.author, Some(guild.id), discord).await;
+        let header = parse_verdict(&message.content);
+        let rgx = Regex::new(GDOC_LINK).context(InvalidRegexSnafu)?;
+        let doc = match rgx.find(&header.body) {
+            Some(link) => {
+                let url = Url::parse(link.as_str()).whatever_context("invalid document link")?;
+                Some((fetch_gdoc(&client, &url).await?, url.to_string()))
+            }
+            None => None,
+        };
+        let name = if !header.parties.is_empty() {
+            header.parties.join(" v. ")
+        } else if let Some(((title, _), _)) = &doc {
+            title.clone()
+        } else if !header.case_number.is_empty() {
+            header.case_number.clone()
+        } else {
+            format!("Case {id}")
+        };
+        let (content, link) = match doc {
+            Some(((_, content), link)) => (content, Some(link)),
+            None => (header.body, None),
+        };
+        Ok(CaseLawInfo {
+            name,
+            judges: if header.judges.is_empty() {
+                vec![author.display_name.clone()]
+            } else {
+                header.judges
+            },
+            author,
+            case_number: header.case_number,
+            parties: header.parties,
+            date: match header.date {
+                Some(date) => date,
+                None => message.timestamp.date_naive(),
+            },
+            content,
+            link,
+            attachments: attachment::collect(
+                message.attachments,
+                Some(guild.id),
+                guild.case_src,
+                id,
+            ),
+        })
+    }
+
+    fn get_content(&self) -> &str {
+        &self.content
+    }
+
+    fn get_title(&self) -> &str {
+        &self.name
+    }
+
+    fn get_date(&self) -> &NaiveDate {
+        &self.date
+    }
+
+    fn get_author(&self) -> &Author {
+        &self.author
+    }
+
+    fn get_mut_content(&mut self) -> &mut String {
+        &mut self.content
+    }
+
+    fn get_mut_title(&mut self) -> &mut String {
+        &mut self.name
+    }
+
+    fn get_mut_date(&mut self) -> &mut NaiveDate {
+        &mut self.date
+    }
+
+    fn get_mut_author(&mut self) -> &mut Author {
+        &mut self.author
+    }
+
+    fn get_attachments(&self) -> &[Attachment] {
+        &self.attachments
+    }
+
+    /// Verdicts posted as a Google Doc are already converted by [fetch_gdoc]
+    fn is_discord_markdown(&self) -> bool {
+        self.link.is_none()
+    }
+}
+
+/// Wrapper around [handle_case_id] accepting a message link or ID, see [discord::document_id]
+pub async fn handle_case(
+    case_link: &str,
+    template: &str,
+    guild: &GuildInfo,
+    opts: &UploadOptions,
+) -> Result<PublishOutcome, Error> {
+    let id = discord::document_id(case_link, guild, Target::CaseLaw)?;
+    handle_case_id(template, id, guild, opts).await
+}
+
+/// Scrapes the verdict, then formats, stores and publishes it with [archive]
+pub async fn handle_case_id(
+    template: &str,
+    case_id: u64,
+    guild: &GuildInfo,
+    opts: &UploadOptions,
+) -> Result<PublishOutcome, Error> {
+    ensure_whatever!(
+        guild.case_src != 0,
+        "no court verdict channel configured for {}",
+        guild.name
+    );
+    let case = CaseLawInfo::scrape(case_id, None, Arc::new(Client::new()), Some(guild)).await?;
+    let templater = templater(template)?;
+    archive(case, case_id, guild, Target::CaseLaw, Utc::now(), &templater, opts).await
+}
+
+/// Renders stored verdicts again, see [rerender]
+pub async fn handle_case_rerender(
+    template: &str,
+    guild: &GuildInfo,
+    case_id: Option<u64>,
+    opts: &UploadOptions,
+) -> Result<Vec<(u64, Error)>, Error> {
+    let templater = templater(template)?;
+    rerender::<CaseLawInfo>(case_id, guild, Target::CaseLaw, &templater, opts).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_verdict_header() {
+        let header = parse_verdict(
+            "**Case No:** 2025-CV-12\nParties: Alice v. Bob\nJudges: Carol, Dave and Erin\nDate: 2025-03-10\n\nThe court finds for the plaintiff.\nSo ordered.",
+        );
+        assert_eq!(
+            header,
+            VerdictHeader {
+                case_number: "2025-CV-12".to_string(),
+                judges: vec!["Carol".to_string(), "Dave".to_string(), "Erin".to_string()],
+                parties: vec!["Alice".to_string(), "Bob".to_string()],
+                date: NaiveDate::from_ymd_opt(2025, 3, 10),
+                body: "The court finds for the plaintiff.\nSo ordered.".to_string(),
+            }
+        );
+    }
+    #[test]
+    fn parse_verdict_no_header() {
+        let header = parse_verdict("Opinion of the court: dismissed.");
+        assert_eq!(header.body, "Opinion of the court: dismissed.");
+        assert!(header.parties.is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+use chrono::Datelike;
+use tokio::sync::MutexGuard;
+
+use crate::{config::Config, error::Error, guilds::GuildInfo, mediawiki, regex::Target};
+
+use super::{EditParams, PageData};
+
+/// Categories of a document: the guild defaults for `target` followed by `extra`.
+/// `{year}`, `{author}` and `{title}` are filled in from the document,
+/// empty and duplicate categories are dropped.
+pub fn categories<'a, T: PageData<'a>>(
+    doc: &T,
+    guild: &GuildInfo,
+    target: Target,
+    extra: &[String],
+) -> Vec<String> {
+    let mut categories = Vec::new();
+    for category in guild.categories.get(target).iter().chain(extra) {
+        let category = category
+            .replace("{year}", &doc.get_date().year().to_string())
+            .replace("{author}", &doc.get_author().username)
+            .replace("{title}", doc.get_title());
+        let category = category.trim();
+        if !category.is_empty() && !categories.iter().any(|c| c == category) {
+            categories.push(category.to_string());
+        }
+    }
+    categories
+}
+
+/// Appends a category link for every category `rendered` does not link to yet
+pub fn append(rendered: &str, categories: &[String]) -> String {
+    let missing = categories
+        .iter()
+        .filter(|c| !rendered.contains(&format!("[[Category:{c}]]")))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return rendered.to_string();
+    }
+    let mut page = format!("{}\n", rendered.trim_end());
+    for category in missing {
+        page.push_str(&format!("\n[[Category:{category}]]"));
+    }
+    page
+}
+
+/// Creates the pages of categories that do not exist yet, using [crate::config::CategorySettings::description]
+pub async fn create_missing(categories: &[String], cfg: &MutexGuard<'_, Config>) -> Result<(), Error> {
+    if categories.is_empty() || !cfg.category.create {
+        return Ok(());
+    }
+    let mut wiki = mediawiki::shared(cfg).await?;
+    let missing = wiki.categories(categories).await?;
+    for category in missing.iter().filter(|c| !c.exists) {
+        let title = format!("Category:{}", category.name);
+        let params = EditParams {
+            summary: "Create category".to_string(),
+            bot: cfg.edit.bot,
+            createonly: true,
+            ..Default::default()
+        };
+        let content = cfg.category.description.replace("{category}", &category.name);
+        match wiki.edit(&title, &content, &params).await {
+            // created by someone else in the meantime
+            Ok(_) | Err(Error::PageExists { .. }) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    wiki.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{converters::legislation::LawInfo, regex::PerTarget};
+
+    #[test]
+    fn document_categories() {
+        let guild = GuildInfo {
+            id: 1,
+            name: "Test".to_string(),
+            eo_src: 0,
+            leg_src: 0,
+            case_src: 0,
+            categories: PerTarget {
+                legislation: vec!["Laws".to_string(), "Laws of {year}".to_string()],
+                ..Default::default()
+            },
+            index: Default::default(),
+            mentions: Default::default(),
+        };
+        let law = LawInfo {
+            author: "f3rri5_".to_string().into(),
+            date: NaiveDate::from_ymd_opt(2025, 3, 10).unwrap(),
+            name: "Some Act".to_string(),
+            content: String::new(),
+            link: String::new(),
+            attachments: vec![],
+        };
+        let extra = ["Laws by {author}".to_string(), "Laws".to_string(), " ".to_string()];
+        let categories = categories(&law, &guild, Target::Legislation, &extra);
+        assert_eq!(categories, vec!["Laws", "Laws of 2025", "Laws by f3rri5_"]);
+        assert!(super::categories(&law, &guild, Target::EO, &[]).is_empty());
+        assert_eq!(
+            append("Text\n[[Category:Laws]]\n", &categories),
+            "Text\n[[Category:Laws]]\n\n[[Category:Laws of 2025]]\n[[Category:Laws by f3rri5_]]"
+        );
+        assert_eq!(append("Text\n", &[]), "Text\n");
+    }
+}
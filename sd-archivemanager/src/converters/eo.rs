@@ -1,115 +1,82 @@
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{NaiveDate, TimeDelta, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use snafu::{OptionExt, ResultExt};
+use snafu::ensure_whatever;
 use std::sync::Arc;
-use tinytemplate::TinyTemplate;
-use tokio::{
-    sync::MutexGuard,
-    task::{self},
-    try_join,
-};
-use xdg::BaseDirectories;
+use tokio::{sync::MutexGuard, try_join};
 
 use crate::{
-    config::Config, error::{Error, ReqwestSnafu, TokioSnafu, XdgSnafu}, format_auth, guilds::GuildInfo, regex::Target, CONFIG
+    config::{Config, EOSettings}, discord::{self, DiscordClient, Message}, error::Error, guilds::GuildInfo, mentions, regex::Target, CONFIG
 };
 
-use super::{PageData, get_cookies, get_token, upload};
+use super::{
+    PageData, PublishOutcome, UploadOptions,
+    attachment::{self, Attachment},
+    author::{self, Author},
+    archive, archive_each, rerender, snowflake_date, templater,
+};
 
 /// Representation of an Executive Order.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct EOInfo {
     pub name: String,
-    pub author: String,
+    #[serde(flatten)]
+    pub author: Author,
     pub content: String,
     pub date: NaiveDate,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+/// List the IDs of all EO threads in [GuildInfo::eo_src], newest first.
+/// Active threads are listed guild-wide and filtered by parent, archived threads are paged
+/// through with the `before` cursor until Discord reports no more.
+/// Threads created before `since` are skipped.
+pub async fn list_eos(
+    guild: &GuildInfo,
+    since: Option<NaiveDate>,
+    discord: &DiscordClient,
+) -> Result<Vec<u64>, Error> {
+    let is_recent = |id: u64| since.is_none_or(|since| snowflake_date(id) >= since);
+    let mut threads = Vec::new();
+    for thread in discord.active_threads(guild.id).await?.threads {
+        if thread.parent_id == Some(guild.eo_src) && is_recent(thread.id) {
+            threads.push(thread.id);
+        }
+    }
+    let mut before = None;
+    loop {
+        let page = discord.archived_threads(guild.eo_src, before).await?;
+        // threads are sorted by archive time, and a thread is archived after it was created
+        let mut reached_since = false;
+        for thread in &page.threads {
+            if is_recent(thread.id) {
+                threads.push(thread.id);
+            }
+            reached_since |= since.is_some_and(|since| {
+                thread
+                    .thread_metadata
+                    .as_ref()
+                    .is_some_and(|meta| meta.archive_timestamp.date_naive() < since)
+            });
+        }
+        match page.threads.last().and_then(|t| t.thread_metadata.as_ref()) {
+            Some(last) if page.has_more && !reached_since => before = Some(last.archive_timestamp),
+            _ => break,
+        }
+    }
+    threads.sort_unstable_by(|a, b| b.cmp(a));
+    threads.dedup();
+    Ok(threads)
 }
 
-/// List all EO threads in a guild.
-// pub fn list_eos(guild: &GuildInfo) -> Result<Vec<EOInfo>, Error> {
-//     let client = Client::new();
-//     let cfg = CONFIG.lock().unwrap();
-//     let req = client
-//         .get(format!(
-//             "https://discord.com/api/v10/guilds/{}/threads/active",
-//             guild.id
-//         ))
-//         .header("Authorization", format_auth(&cfg));
-//     let mut threads = Vec::new();
-//     while {
-//         let json = req
-//             .try_clone()
-//             .unwrap()
-//             .send()
-//             .unwrap()
-//             .json::<Value>()
-//             .unwrap();
-//         for thread in json["threads"].as_array().unwrap() {
-//             if thread["parent_id"]
-//                 .as_str()
-//                 .unwrap()
-//                 .parse::<u64>()
-//                 .whatever_context("invalid response from discord")?
-//                 == guild.eo_src
-//             {
-//                 threads.push(scrape_eo(
-//                     thread["id"]
-//                         .as_str()
-//                         .unwrap()
-//                         .parse::<u64>()
-//                         .whatever_context("Invalid response from discord")?,
-//                     Some(&cfg),
-//                     &client,
-//                 )?);
-//             }
-//         }
-//         json["has_more"].as_bool().unwrap()
-//     } {}
-//     let req = client
-//         .get(format!(
-//             "https://discord.com/api/v10/channels/{}/threads/archived/public",
-//             guild.eo_src
-//         ))
-//         .header("Authorization", format_auth(&cfg));
-//     while {
-//         let json = req
-//             .try_clone()
-//             .unwrap()
-//             .send()
-//             .unwrap()
-//             .json::<Value>()
-//             .unwrap();
-//         for thread in json["threads"].as_array().unwrap() {
-//             if thread["parent_id"]
-//                 .as_str()
-//                 .unwrap()
-//                 .parse::<u64>()
-//                 .whatever_context("invalid response from discord")?
-//                 == guild.eo_src
-//             {
-//                 threads.push(scrape_eo(
-//                     thread["id"]
-//                         .as_str()
-//                         .unwrap()
-//                         .parse::<u64>()
-//                         .whatever_context("Invalid response from discord")?,
-//                     Some(&cfg),
-//                     &client,
-//                 )?);
-//             }
-//         }
-//         json["has_more"].as_bool().unwrap()
-//     } {}
-//     Ok(threads)
-// }
 impl<'a> PageData<'a> for EOInfo {
     /// Create an EO object by scraping a message
     async fn scrape(
         id: u64,
         conf: Option<&MutexGuard<'_, Config>>,
-        client: Arc<Client>,
-        _: Option<&GuildInfo>,
+        _client: Arc<Client>,
+        guild: Option<&GuildInfo>,
     ) -> Result<EOInfo, Error> {
         let cfg;
         let lock;
@@ -119,39 +86,41 @@ impl<'a> PageData<'a> for EOInfo {
         } else {
             cfg = conf.unwrap();
         }
-        let auth = format_auth(&cfg);
-        #[derive(Debug, Deserialize)]
-        struct MsgInfo {
-            author: String,
-            content: String,
-            timestamp: String,
-        }
-        #[derive(Debug, Deserialize)]
-        struct ChannelInfo {
-            name: String,
-        }
-        let (message, channel) = try_join! {
-            client
-            .get(format!(
-                "https://discord.com/api/v10/channels/{id}/messages/{id}"
-            ))
-            .header("Authorization", &auth)
-            .send(), client
-            .get(format!("https://discord.com/api/v10/channels/{id}"))
-            .header("Authorization", &auth)
-            .send()
+        let discord = discord::shared(cfg);
+        let (message, channel, followups) = try_join!(
+            discord.message(id, id),
+            discord.channel(id),
+            discord.messages_after(id, id)
+        )?;
+        if let Some(guild) = guild {
+            ensure_whatever!(
+                channel.parent_id == Some(guild.eo_src),
+                "{id} is not a thread in the EO forum of {}",
+                guild.name
+            );
         }
-        .context(ReqwestSnafu)?;
-        let (message, channel) =
-            try_join!(message.json::<MsgInfo>(), channel.json::<ChannelInfo>()).context(ReqwestSnafu)?;
+        let (continued, amendments) = split_thread(&message, &followups, &cfg.eo);
+        let attachments = [&message]
+            .into_iter()
+            .chain(continued.iter().copied())
+            .chain(amendments.iter().copied())
+            .flat_map(|m| attachment::collect(m.attachments.clone(), guild.map(|g| g.id), id, m.id))
+            .collect();
+        let guild_id = guild.map(|g| g.id).or(channel.guild_id);
+        let content = thread_content(&message, &continued, &amendments, &cfg.eo);
+        let content = mentions::resolve(
+            &content,
+            guild_id,
+            &guild.map(|g| g.mentions.clone()).unwrap_or_default(),
+            discord,
+        )
+        .await;
         Ok(EOInfo {
-            author: message.author,
-            name: channel.name,
-            content: message.content,
-            date: message.timestamp
-                .parse::<DateTime<Utc>>()
-                .unwrap()
-                .date_naive(),
+            content,
+            author: author::fetch(message.author, guild_id, discord).await,
+            name: channel.name.unwrap_or_default(),
+            date: message.timestamp.date_naive(),
+            attachments,
         })
     }
 
@@ -167,7 +136,7 @@ impl<'a> PageData<'a> for EOInfo {
         &self.date
     }
 
-    fn get_author(&self) -> &str {
+    fn get_author(&self) -> &Author {
         &self.author
     }
 
@@ -183,59 +152,188 @@ impl<'a> PageData<'a> for EOInfo {
         &mut self.date
     }
 
-    fn get_mut_author(&mut self) -> &mut String {
+    fn get_mut_author(&mut self) -> &mut Author {
         &mut self.author
     }
+
+    fn get_attachments(&self) -> &[Attachment] {
+        &self.attachments
+    }
+
+    fn is_discord_markdown(&self) -> bool {
+        true
+    }
 }
 
-/// Wrapper around [handle_eo_id]. Extracts id from the link and passes it on
-pub async fn handle_eo(eo_link: String, template: &str) -> Result<(), Error> {
-    let id = eo_link
-        .split('/')
-        .nth(5)
-        .whatever_context("invalid url")?
-        .trim()
-        .parse::<u64>()
-        .whatever_context("invalid url")?;
-    handle_eo_id(template, id).await?;
-    Ok(())
+/// Splits the follow-ups of a thread's starter message into the messages continuing it and
+/// later amendments, both by the author of the starter message only.
+/// A message continues the EO if [EOSettings::merge] is set and nobody else posted since the
+/// previous one and it came within [EOSettings::merge_window]; every later message of the
+/// author is an amendment if [EOSettings::amendments] is set.
+fn split_thread<'m>(
+    starter: &Message,
+    followups: &'m [Message],
+    rules: &EOSettings,
+) -> (Vec<&'m Message>, Vec<&'m Message>) {
+    let mut continued = Vec::new();
+    let mut amendments = Vec::new();
+    let mut previous = starter;
+    let mut continuing = rules.merge;
+    for message in followups {
+        let by_author = message.author.id == starter.author.id;
+        continuing &= by_author
+            && (rules.merge_window == 0
+                || message.timestamp - previous.timestamp
+                    <= TimeDelta::minutes(rules.merge_window as i64));
+        if continuing {
+            continued.push(message);
+        } else if by_author && rules.amendments {
+            amendments.push(message);
+        }
+        previous = message;
+    }
+    (continued, amendments)
 }
 
-/// Formats the EO, puts it in a template and uploads
-pub async fn handle_eo_id(template: &str, eo_id: u64) -> Result<(), Error> {
-    let xdg = BaseDirectories::with_prefix("sd-archivemanager").context(XdgSnafu)?;
-    let (file, cookies) = get_cookies(&xdg).await?;
-    let client = Arc::new(
-        Client::builder()
-            .cookie_provider(Arc::clone(&cookies))
-            .build()
-            .unwrap(),
-    );
-    let eo_client = Arc::clone(&client);
-    let eo_handle = task::spawn(async move { EOInfo::scrape(eo_id, None, eo_client, None) });
-    let mut templater = TinyTemplate::new();
-    templater
-        .add_template("template", &template)
-        .whatever_context("invalid template")?;
-    let cfg = CONFIG.lock().await;
-    let mut eo = eo_handle.await.context(TokioSnafu)?.await?;
-    eo.format(Target::EO, &cfg).await?;
-    upload(
-        &eo.name,
-        &client,
-        &get_token(&cfg, &client, &xdg).await?,
-        &cfg,
-        &templater
-            .render("template", &eo)
-            .whatever_context("failed to render")?,
-    )
-    .await?;
-    cookies
-        .lock()
-        .unwrap()
-        .save(&mut file.into_std().await, |s| serde_json::to_string(s))
-        .expect("failed to save cookies");
-    Ok(())
+/// Content of the starter message and the messages continuing it joined with
+/// [EOSettings::separator], followed by a section with the amendments
+fn thread_content(
+    starter: &Message,
+    continued: &[&Message],
+    amendments: &[&Message],
+    rules: &EOSettings,
+) -> String {
+    let mut content = [starter]
+        .into_iter()
+        .chain(continued.iter().copied())
+        .map(|m| m.content.trim())
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>()
+        .join(&rules.separator);
+    if !amendments.is_empty() {
+        content.push_str(&format!("\n\n# {}", rules.amendments_heading));
+        for amendment in amendments {
+            content.push_str(&format!(
+                "\n## {}\n{}\n",
+                amendment.timestamp.format("%Y-%m-%d"),
+                amendment.content.trim()
+            ));
+        }
+    }
+    content
+}
+
+/// Wrapper around [handle_eo_id] accepting a thread link or ID, see [discord::document_id]
+pub async fn handle_eo(
+    eo_link: &str,
+    template: &str,
+    guild: &GuildInfo,
+    opts: &UploadOptions,
+) -> Result<PublishOutcome, Error> {
+    let id = discord::document_id(eo_link, guild, Target::EO)?;
+    handle_eo_id(template, id, guild, opts).await
+}
+
+/// Scrapes the EO, then formats, stores and publishes it with [archive]
+pub async fn handle_eo_id(
+    template: &str,
+    eo_id: u64,
+    guild: &GuildInfo,
+    opts: &UploadOptions,
+) -> Result<PublishOutcome, Error> {
+    let templater = templater(template)?;
+    let eo = EOInfo::scrape(eo_id, None, Arc::new(Client::new()), Some(guild)).await?;
+    archive(eo, eo_id, guild, Target::EO, Utc::now(), &templater, opts).await
+}
+
+/// Renders stored EOs again, see [rerender]
+pub async fn handle_eo_rerender(
+    template: &str,
+    guild: &GuildInfo,
+    eo_id: Option<u64>,
+    opts: &UploadOptions,
+) -> Result<Vec<(u64, Error)>, Error> {
+    let templater = templater(template)?;
+    rerender::<EOInfo>(eo_id, guild, Target::EO, &templater, opts).await
+}
+
+/// Archives every EO in [GuildInfo::eo_src] created on or after `since`.
+/// EOs that did not change since their last upload are skipped, see [UploadOptions::skip_unchanged].
+/// Failures are returned with the thread ID, see [archive_each].
+pub async fn handle_eo_sync(
+    template: &str,
+    guild: &GuildInfo,
+    since: Option<NaiveDate>,
+    opts: &UploadOptions,
+) -> Result<Vec<(u64, Error)>, Error> {
+    let discord = discord::shared(&*CONFIG.lock().await);
+    let ids = list_eos(guild, since, discord).await?;
+    let opts = UploadOptions {
+        skip_unchanged: true,
+        ..opts.clone()
+    };
+    let failed = archive_each(ids, guild, Target::EO, &opts, async |id, opts| {
+        handle_eo_id(template, id, guild, opts).await
+    })
+    .await;
+    Ok(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::markdown;
+
+    #[test]
+    fn merge_thread() {
+        let message = |id: u64, author: u64, minute: u32, content: &str| {
+            serde_json::from_value::<Message>(serde_json::json!({
+                "id": id.to_string(), "channel_id": "1", "content": content,
+                "author": {"id": author.to_string(), "username": format!("user{author}")},
+                "timestamp": format!("2025-03-10T20:{minute:02}:00+00:00"),
+            }))
+            .unwrap()
+        };
+        let starter = message(1, 7, 0, "Section 1");
+        let followups = vec![
+            message(2, 7, 5, "Section 2"),
+            message(3, 7, 50, "Section 3"),
+            message(4, 8, 51, "Congrats!"),
+            message(5, 7, 52, "Section 2 is repealed."),
+        ];
+        let mut rules = EOSettings::default();
+        let (continued, amendments) = split_thread(&starter, &followups, &rules);
+        assert_eq!(
+            thread_content(&starter, &continued, &amendments, &rules),
+            "Section 1\n\nSection 2"
+        );
+        rules.merge_window = 0;
+        rules.amendments = true;
+        let (continued, amendments) = split_thread(&starter, &followups, &rules);
+        assert_eq!(
+            thread_content(&starter, &continued, &amendments, &rules),
+            "Section 1\n\nSection 2\n\nSection 3\n\n# Amendments\n## 2025-03-10\nSection 2 is repealed.\n"
+        );
+        rules.merge = false;
+        let (continued, amendments) = split_thread(&starter, &followups, &rules);
+        assert!(continued.is_empty());
+        assert_eq!(amendments.len(), 3);
+    }
+    #[test]
+    fn render_unescaped() {
+        let eo = EOInfo {
+            name: "EO 12".to_string(),
+            author: "f3rri5_".to_string().into(),
+            content: markdown::to_wikitext("**bold** __under__\n> quoted"),
+            date: NaiveDate::from_ymd_opt(2025, 3, 10).unwrap(),
+            attachments: Vec::new(),
+        };
+        let templater = templater("{content}").unwrap();
+        assert_eq!(
+            templater.render("template", &eo).unwrap(),
+            "'''bold''' <u>under</u>\n\n<blockquote>quoted</blockquote>"
+        );
+    }
 }
 
 // #[cfg(test)]
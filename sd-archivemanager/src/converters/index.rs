@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+use tokio::sync::MutexGuard;
+
+use crate::{
+    CONFIG,
+    config::Config,
+    error::Error,
+    guilds::GuildInfo,
+    ledger::Ledger,
+    regex::Target,
+    store::Store,
+};
+
+use super::{
+    EditParams, PageData, PublishOutcome, UploadMode, UploadOptions, case_law::CaseLawInfo,
+    eo::EOInfo, legislation::LawInfo, publish,
+};
+
+/// One archived document in an index page
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexRow {
+    /// Title of the wiki page
+    pub title: String,
+    pub author: String,
+    pub date: NaiveDate,
+    /// Link to the discord message the document was scraped from
+    pub source: String,
+}
+
+/// Rows of every stored record of `target` that made it to the wiki, using the title
+/// the [Ledger] recorded for it
+async fn rows<'a, T: PageData<'a>>(
+    store: &Store,
+    ledger: &Ledger,
+    guild: &GuildInfo,
+    target: Target,
+) -> Result<Vec<IndexRow>, Error> {
+    let channel = |id: u64| match target {
+        // every EO is a forum thread of its own
+        Target::EO => id,
+        Target::Legislation => guild.leg_src,
+        Target::CaseLaw => guild.case_src,
+    };
+    let mut rows = Vec::new();
+    for id in store.list(guild.id, target).await? {
+        let Some(entry) = ledger.get(guild.id, target, id) else {
+            continue;
+        };
+        let Some(record) = store.load::<T>(guild.id, target, id).await? else {
+            continue;
+        };
+        rows.push(IndexRow {
+            title: entry.title.clone(),
+            author: record.formatted.get_author().display_name.clone(),
+            date: *record.formatted.get_date(),
+            source: format!(
+                "https://discord.com/channels/{}/{}/{id}",
+                guild.id,
+                channel(id)
+            ),
+        });
+    }
+    Ok(rows)
+}
+
+/// Renders a sortable wikitable per year, newest year first and oldest document first
+pub fn render_index(rows: &[IndexRow]) -> String {
+    let mut years = BTreeMap::<i32, Vec<&IndexRow>>::new();
+    for row in rows {
+        years.entry(row.date.year()).or_default().push(row);
+    }
+    let mut page =
+        "<!-- Generated by sd-archivemanager, manual edits will be overwritten -->\n".to_string();
+    for (year, mut rows) in years.into_iter().rev() {
+        rows.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.title.cmp(&b.title)));
+        page.push_str(&format!(
+            "\n== {year} ==\n{{| class=\"wikitable sortable\"\n! Title !! Author !! Date !! Source\n"
+        ));
+        for row in rows {
+            page.push_str(&format!(
+                "|-\n| [[{}]] || {} || {} || [{} Discord]\n",
+                row.title,
+                row.author.replace('|', "{{!}}"),
+                row.date.format("%Y-%m-%d"),
+                row.source
+            ));
+        }
+        page.push_str("|}\n");
+    }
+    page
+}
+
+/// Regenerates the index page of `target` from the local [Store].
+/// Returns [None] if the guild has no index page for the target.
+pub async fn update_index(
+    guild: &GuildInfo,
+    target: Target,
+    opts: &UploadOptions,
+    cfg: &MutexGuard<'_, Config>,
+) -> Result<Option<PublishOutcome>, Error> {
+    let Some(title) = guild.index.get(target).as_deref() else {
+        return Ok(None);
+    };
+    let store = Store::open()?;
+    let ledger = Ledger::load().await?;
+    let rows = match target {
+        Target::EO => rows::<EOInfo>(&store, &ledger, guild, target).await?,
+        Target::Legislation => rows::<LawInfo>(&store, &ledger, guild, target).await?,
+        Target::CaseLaw => rows::<CaseLawInfo>(&store, &ledger, guild, target).await?,
+    };
+    let params = EditParams {
+        summary: "Update index".to_string(),
+        bot: cfg.edit.bot,
+        minor: cfg.edit.minor || opts.minor,
+        tags: cfg.edit.tags.iter().chain(&opts.tags).cloned().collect(),
+        ..Default::default()
+    };
+    // the index is regenerated as a whole, there is nothing to confirm, and it is
+    // created or updated regardless of the mode the documents were uploaded with
+    let opts = UploadOptions {
+        confirm: false,
+        mode: UploadMode::Overwrite,
+        ..opts.clone()
+    };
+    Ok(Some(
+        publish(title, None, &render_index(&rows), &[], &params, &opts, cfg).await?,
+    ))
+}
+
+/// [update_index] for when the config is not locked yet
+pub async fn rebuild(
+    guild: &GuildInfo,
+    target: Target,
+    opts: &UploadOptions,
+) -> Result<Option<PublishOutcome>, Error> {
+    update_index(guild, target, opts, &CONFIG.lock().await).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_years() {
+        let row = |title: &str, date: (i32, u32, u32)| IndexRow {
+            title: title.to_string(),
+            author: "f3rri5_".to_string(),
+            date: NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+            source: format!("https://discord.com/channels/1/2/{}", date.0),
+        };
+        let page = render_index(&[
+            row("EO 2", (2024, 5, 1)),
+            row("EO 4", (2025, 2, 1)),
+            row("EO 3", (2025, 1, 1)),
+        ]);
+        assert_eq!(
+            page,
+            r#"<!-- Generated by sd-archivemanager, manual edits will be overwritten -->
+
+== 2025 ==
+{| class="wikitable sortable"
+! Title !! Author !! Date !! Source
+|-
+| [[EO 3]] || f3rri5_ || 2025-01-01 || [https://discord.com/channels/1/2/2025 Discord]
+|-
+| [[EO 4]] || f3rri5_ || 2025-02-01 || [https://discord.com/channels/1/2/2025 Discord]
+|}
+
+== 2024 ==
+{| class="wikitable sortable"
+! Title !! Author !! Date !! Source
+|-
+| [[EO 2]] || f3rri5_ || 2024-05-01 || [https://discord.com/channels/1/2/2024 Discord]
+|}
+"#
+        );
+    }
+}
@@ -1,27 +1,33 @@
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{NaiveDate, Utc};
 use regex::Regex;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use snafu::{OptionExt, ResultExt};
 use std::sync::Arc;
-use tinytemplate::{format_unescaped, TinyTemplate};
-use tokio::{sync::MutexGuard, try_join};
+use tokio::sync::MutexGuard;
 use url::Url;
 
 use crate::{
-    config::Config, error::{Error, InvalidRegexSnafu, ReqwestSnafu, XdgSnafu}, format_auth, guilds::GuildInfo, regex::Target, CONFIG
+    config::Config, discord::{self, DiscordClient}, error::{Error, InvalidRegexSnafu}, guilds::GuildInfo, regex::Target, CONFIG
 };
 
-use super::{PageData, get_cookies, get_token, upload};
+use super::{
+    PageData, PublishOutcome, UploadOptions,
+    attachment::{self, Attachment},
+    author::{self, Author},
+    GDOC_LINK, archive, archive_each, date_snowflake, fetch_gdoc, rerender, templater,
+};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LawInfo {
-    pub author: String,
+    #[serde(flatten)]
+    pub author: Author,
     pub date: NaiveDate,
     pub name: String,
     pub content: String,
     pub link: String,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
 }
 
 impl<'a> PageData<'a> for LawInfo {
@@ -39,71 +45,24 @@ impl<'a> PageData<'a> for LawInfo {
         } else {
             cfg = conf.unwrap();
         }
-        #[derive(Debug, Deserialize)]
-        struct MsgInfo {
-            author: Value,
-            timestamp: String,
-            content: String,
-        }
-        let message = client
-            .get(format!(
-                "https://discord.com/api/v10/channels/{}/messages/{}",
-                guild.unwrap().leg_src,
-                id
-            ))
-            .header("Authorization", format_auth(cfg))
-            .send()
-            .await
-            .context(ReqwestSnafu)?
-            .json::<MsgInfo>()
-            .await
-            .context(ReqwestSnafu)?;
-        let rgx = Regex::new(r#"(https:\/\/[^])\s]+)"#).context(InvalidRegexSnafu)?;
-        let url = Url::parse(
-            rgx.captures(&message.content)
-                .unwrap()
-                .extract::<1>()
-                .1[0],
-        )
-        .unwrap();
-        let id = url
-            .path_segments()
-            .unwrap()
-            .nth(url.path_segments().unwrap().position(|x| x == "d").unwrap() + 1)
-            .unwrap();
-        let (content, title) = try_join! {client
-        .get(format!(
-            "https://docs.google.com/document/d/{}/export?format=md",
-            id
-        ))
-        .send(), client
-            .get(format!("https://docs.google.com/document/d/{}/view", id))
-            .send()}
-        .context(ReqwestSnafu)?;
-        let (content, title) = try_join!(content.text(), title.text()).context(ReqwestSnafu)?;
-        let name = {
-            let re = Regex::new(r"<title>(.*?)</title>").context(InvalidRegexSnafu)?;
-            if let Some(captures) = re.captures(&title) {
-                let raw_title = captures.get(1).map_or("", |m| m.as_str());
-                let cleaned_title = raw_title
-                    .strip_suffix(" - Google Docs")
-                    .unwrap_or(raw_title);
-                cleaned_title.to_string()
-            } else {
-                "".to_string()
-            }
-        };
+        let guild = guild.whatever_context("legislation requires a guild")?;
+        let discord = discord::shared(cfg);
+        let message = discord.message(guild.leg_src, id).await?;
+        let rgx = Regex::new(GDOC_LINK).context(InvalidRegexSnafu)?;
+        let link = rgx
+            .find(&message.content)
+            .whatever_context(format!("message {id} does not link a Google Doc"))?;
+        let url = Url::parse(link.as_str()).whatever_context("invalid document link")?;
+        let (name, content) = fetch_gdoc(&client, &url).await?;
+        let attachments =
+            attachment::collect(message.attachments, Some(guild.id), guild.leg_src, id);
         Ok(LawInfo {
-            author: message.author["username"]
-                .as_str().unwrap()
-                .to_string(),
-            date: message.timestamp
-                .parse::<DateTime<Utc>>()
-                .unwrap()
-                .date_naive(),
+            author: author::fetch(message.author, Some(guild.id), discord).await,
+            date: message.timestamp.date_naive(),
             name,
             content,
             link: url.to_string(),
+            attachments,
         })
     }
 
@@ -119,7 +78,7 @@ impl<'a> PageData<'a> for LawInfo {
         &self.date
     }
 
-    fn get_author(&self) -> &str {
+    fn get_author(&self) -> &Author {
         &self.author
     }
 
@@ -135,55 +94,104 @@ impl<'a> PageData<'a> for LawInfo {
         &mut self.date
     }
 
-    fn get_mut_author(&mut self) -> &mut String {
+    fn get_mut_author(&mut self) -> &mut Author {
         &mut self.author
     }
+
+    fn get_attachments(&self) -> &[Attachment] {
+        &self.attachments
+    }
+}
+
+/// Wrapper around [handle_law_id] accepting a message link or ID, see [discord::document_id]
+pub async fn handle_law(
+    law_link: &str,
+    template: &str,
+    guild: &GuildInfo,
+    opts: &UploadOptions,
+) -> Result<PublishOutcome, Error> {
+    let id = discord::document_id(law_link, guild, Target::Legislation)?;
+    handle_law_id(template, id, guild, opts).await
+}
+
+/// Scrapes the law, then formats, stores and publishes it with [archive]
+pub async fn handle_law_id(
+    template: &str,
+    law_id: u64,
+    guild: &GuildInfo,
+    opts: &UploadOptions,
+) -> Result<PublishOutcome, Error> {
+    let law = LawInfo::scrape(law_id, None, Arc::new(Client::new()), Some(guild)).await?;
+    let templater = templater(template)?;
+    archive(law, law_id, guild, Target::Legislation, Utc::now(), &templater, opts).await
+}
+
+/// Renders stored laws again, see [rerender]
+pub async fn handle_law_rerender(
+    template: &str,
+    guild: &GuildInfo,
+    law_id: Option<u64>,
+    opts: &UploadOptions,
+) -> Result<Vec<(u64, Error)>, Error> {
+    let templater = templater(template)?;
+    rerender::<LawInfo>(law_id, guild, Target::Legislation, &templater, opts).await
 }
 
-pub async fn handle_law(law_link: String, template: &str, guild: &GuildInfo) -> Result<(), Error> {
-    let id = law_link
-        .split('/')
-        .nth(5)
-        .whatever_context("invalid url")?
-        .trim()
-        .parse::<u64>()
-        .whatever_context("invalid url")?;
-    handle_law_id(template, id, guild).await?;
-    Ok(())
+/// List the IDs of all messages in [GuildInfo::leg_src] that link a Google Doc, newest first.
+/// The channel history is paged through with `before` cursors, starting at the end of `until`
+/// (or the latest message) and stopping at the start of `since` or after `limit` matches.
+pub async fn list_laws(
+    guild: &GuildInfo,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    limit: Option<usize>,
+    discord: &DiscordClient,
+) -> Result<Vec<u64>, Error> {
+    let doc = Regex::new(GDOC_LINK).context(InvalidRegexSnafu)?;
+    let after = since.map(date_snowflake).unwrap_or(0);
+    let mut before = until
+        .and_then(|until| until.succ_opt())
+        .map(date_snowflake);
+    let mut laws = Vec::new();
+    loop {
+        let page = discord.messages(guild.leg_src, before, 100).await?;
+        let full = page.len() == 100;
+        for msg in page {
+            let id = msg.id;
+            if id < after || limit.is_some_and(|limit| laws.len() >= limit) {
+                return Ok(laws);
+            }
+            if doc.is_match(&msg.content) {
+                laws.push(id);
+            }
+            before = Some(id);
+        }
+        if !full {
+            return Ok(laws);
+        }
+    }
 }
 
-pub async fn handle_law_id(template: &str, law_id: u64, guild: &GuildInfo) -> Result<(), Error> {
-    let xdg = xdg::BaseDirectories::with_prefix("sd-archivemanager").context(XdgSnafu)?;
-    let (file, cookies) = get_cookies(&xdg).await?;
-    let client = Arc::new(
-        Client::builder()
-            .cookie_provider(Arc::clone(&cookies))
-            .build()
-            .unwrap(),
-    );
-    let law_client = Arc::clone(&client);
-    let mut law = LawInfo::scrape(law_id, None, law_client, Some(guild))
-        .await?;
-    let mut templater: TinyTemplate<'_> = TinyTemplate::new();
-    templater.set_default_formatter(&format_unescaped);
-    templater
-        .add_template("template", &template)
-        .whatever_context("invalid template")?;
-    let cfg = CONFIG.lock().await;
-    law.format(Target::Legislation, &cfg).await?;
-    upload(
-        &law.name,
-        &client,
-        &get_token(&cfg, &client, &xdg).await?,
-        &cfg,
-        &templater
-        .render("template", &law)
-        .whatever_context("failed to render")?,
-    ).await?;
-    cookies
-        .lock()
-        .unwrap()
-        .save(&mut file.into_std().await, |s| serde_json::to_string(s))
-        .expect("failed to save cookies");
-    Ok(())
+/// Archives every law in [GuildInfo::leg_src] matching the filters of [list_laws].
+/// Laws that did not change since their last upload are skipped, see [UploadOptions::skip_unchanged].
+/// Failures are returned with the message ID, see [archive_each].
+pub async fn handle_law_sync(
+    template: &str,
+    guild: &GuildInfo,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    limit: Option<usize>,
+    opts: &UploadOptions,
+) -> Result<Vec<(u64, Error)>, Error> {
+    let discord = discord::shared(&*CONFIG.lock().await);
+    let ids = list_laws(guild, since, until, limit, discord).await?;
+    let opts = UploadOptions {
+        skip_unchanged: true,
+        ..opts.clone()
+    };
+    let failed = archive_each(ids, guild, Target::Legislation, &opts, async |id, opts| {
+        handle_law_id(template, id, guild, opts).await
+    })
+    .await;
+    Ok(failed)
 }
@@ -0,0 +1,285 @@
+use mwtext_parser::ast::{Block, Document, Heading, Inline, Inlines};
+
+/// Converts Discord markdown to wikitext
+pub fn to_wikitext(content: &str) -> String {
+    parse(content).to_string()
+}
+
+/// Parses Discord markdown into wikitext nodes
+pub fn parse(content: &str) -> Document {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut lines = content.lines().peekable();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        if let Some(rest) = trimmed.strip_prefix("```") {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Preformatted(code_block(rest, &mut lines)));
+        } else if let Some(rest) = trimmed.strip_prefix(">>>") {
+            // quotes everything up to the end of the message
+            flush(&mut paragraph, &mut blocks);
+            let quoted = std::iter::once(rest.trim_start())
+                .chain(lines.by_ref())
+                .collect::<Vec<_>>()
+                .join("\n");
+            blocks.push(Block::Quote(parse_inline(quoted.trim_matches('\n'))));
+        } else if quote_line(trimmed).is_some() {
+            flush(&mut paragraph, &mut blocks);
+            let mut quoted = vec![quote_line(trimmed).unwrap()];
+            while let Some(line) = lines.peek().and_then(|l| quote_line(l.trim_start())) {
+                quoted.push(line);
+                lines.next();
+            }
+            blocks.push(Block::Quote(parse_inline(&quoted.join("\n"))));
+        } else if let Some((level, text)) = heading(trimmed) {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Heading(Heading {
+                // level 1 is the page title on the wiki
+                level: level + 1,
+                text: Inlines(&parse_inline(text)).to_string(),
+            }));
+        } else if let Some((ordered, depth, text)) = list_item(line) {
+            flush(&mut paragraph, &mut blocks);
+            let mut items = vec![(depth, parse_inline(text))];
+            while let Some((_, depth, text)) = lines
+                .peek()
+                .and_then(|l| list_item(l))
+                .filter(|item| item.0 == ordered)
+            {
+                items.push((depth, parse_inline(text)));
+                lines.next();
+            }
+            blocks.push(Block::List { ordered, items });
+        } else if trimmed.is_empty() {
+            flush(&mut paragraph, &mut blocks);
+        } else {
+            // subtext has no wiki equivalent and is kept as normal text
+            paragraph.push(trimmed.strip_prefix("-# ").unwrap_or(trimmed));
+        }
+    }
+    flush(&mut paragraph, &mut blocks);
+    Document(blocks)
+}
+
+fn flush(paragraph: &mut Vec<&str>, blocks: &mut Vec<Block>) {
+    if !paragraph.is_empty() {
+        blocks.push(Block::Paragraph(parse_inline(&paragraph.join("\n"))));
+        paragraph.clear();
+    }
+}
+
+/// Reads a fenced code block whose opening line continues with `rest`.
+/// A single word right after the fence names the language and is dropped.
+fn code_block<'a>(rest: &'a str, lines: &mut impl Iterator<Item = &'a str>) -> String {
+    if let Some(code) = rest.strip_suffix("```").filter(|c| !c.is_empty()) {
+        return code.to_string();
+    }
+    let mut code = Vec::new();
+    if rest.contains(char::is_whitespace) {
+        code.push(rest);
+    }
+    for line in lines {
+        if let Some(end) = line.trim_end().strip_suffix("```") {
+            if !end.is_empty() {
+                code.push(end);
+            }
+            break;
+        }
+        code.push(line);
+    }
+    code.join("\n")
+}
+
+fn quote_line(line: &str) -> Option<&str> {
+    if line == ">" {
+        return Some("");
+    }
+    line.strip_prefix("> ")
+}
+
+/// `# `, `## ` and `### ` headings with their level
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = line[level..].strip_prefix(' ')?.trim();
+    ((1..=3).contains(&level) && !text.is_empty()).then_some((level, text))
+}
+
+/// List items as whether the list is ordered, the depth from the indentation and the text
+fn list_item(line: &str) -> Option<(bool, usize, &str)> {
+    let trimmed = line.trim_start();
+    let depth = (line.len() - trimmed.len()) / 2 + 1;
+    if let Some(text) = trimmed.strip_prefix("- ").or(trimmed.strip_prefix("* ")) {
+        return Some((false, depth, text));
+    }
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    let text = trimmed[digits..].strip_prefix(". ")?;
+    (digits > 0).then_some((true, depth, text))
+}
+
+/// Delimiters of inline markup, longest first so `**` is not read as two `*`
+const SPANS: [&str; 7] = ["***", "**", "__", "~~", "||", "*", "_"];
+
+pub(crate) fn parse_inline(text: &str) -> Vec<Inline> {
+    let mut nodes = Vec::new();
+    let mut buf = String::new();
+    let mut prev = None;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let parsed = match c {
+            '\\' => rest[1..]
+                .chars()
+                .next()
+                .filter(char::is_ascii_punctuation)
+                .map(|escaped| (Inline::Text(escaped.to_string()), 1 + escaped.len_utf8())),
+            '\n' => Some((Inline::LineBreak, 1)),
+            '`' => code_span(rest),
+            '[' => masked_link(rest),
+            _ => SPANS
+                .iter()
+                .filter(|delim| rest.starts_with(**delim))
+                .find_map(|delim| span(rest, delim, prev)),
+        };
+        match parsed {
+            Some((node, len)) => {
+                if !buf.is_empty() {
+                    nodes.push(Inline::Text(std::mem::take(&mut buf)));
+                }
+                // escaped characters are merged back into the surrounding text
+                match node {
+                    Inline::Text(text) => buf.push_str(&text),
+                    node => nodes.push(node),
+                }
+                prev = rest[..len].chars().last();
+                rest = &rest[len..];
+            }
+            None => {
+                buf.push(c);
+                prev = Some(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if !buf.is_empty() {
+        nodes.push(Inline::Text(buf));
+    }
+    nodes
+}
+
+/// Inline code between one or two backticks
+fn code_span(text: &str) -> Option<(Inline, usize)> {
+    let delim = if text.starts_with("``") { "``" } else { "`" };
+    let end = text[delim.len()..].find(delim).filter(|end| *end > 0)?;
+    let code = &text[delim.len()..delim.len() + end];
+    Some((Inline::Code(code.to_string()), end + 2 * delim.len()))
+}
+
+/// `[text](url)`, the url may be wrapped in `<>` to suppress the embed
+fn masked_link(text: &str) -> Option<(Inline, usize)> {
+    let close = text.find("](")?;
+    let label = &text[1..close];
+    let len = text[close..].find(')')? + close + 1;
+    let url = text[close + 2..len - 1].trim_start_matches('<').trim_end_matches('>');
+    let valid = url.starts_with("https://") || url.starts_with("http://");
+    if label.is_empty() || !valid || url.contains(char::is_whitespace) {
+        return None;
+    }
+    let node = Inline::ExternalLink {
+        url: url.to_string(),
+        text: parse_inline(label),
+    };
+    Some((node, len))
+}
+
+/// Markup opened by `delim` at the start of `text`, `prev` is the character before it
+fn span(text: &str, delim: &str, prev: Option<char>) -> Option<(Inline, usize)> {
+    // underscores inside words like snake_case are not italic
+    if delim == "_" && prev.is_some_and(char::is_alphanumeric) {
+        return None;
+    }
+    let after = &text[delim.len()..];
+    let end = closing(after, delim)?;
+    let inner = parse_inline(&after[..end]);
+    let node = match delim {
+        "***" => Inline::Bold(vec![Inline::Italic(inner)]),
+        "**" => Inline::Bold(inner),
+        "__" => Inline::Underline(inner),
+        "~~" => Inline::Strike(inner),
+        "||" => Inline::Spoiler(inner),
+        _ => Inline::Italic(inner),
+    };
+    Some((node, end + 2 * delim.len()))
+}
+
+/// Position of the delimiter closing `delim` in `after`
+fn closing(after: &str, delim: &str) -> Option<usize> {
+    let single = delim.len() == 1;
+    // `* not italic *`, as in arithmetic
+    if single && after.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut from = 0;
+    while let Some(pos) = after[from..].find(delim).map(|pos| pos + from) {
+        let next = after[pos + delim.len()..].chars().next();
+        if single && next == Some(delim.chars().next().unwrap()) {
+            // part of a double delimiter, e.g. bold inside italic
+            from = pos + 2;
+            continue;
+        }
+        let valid = pos > 0
+            && match delim {
+                "*" | "_" => !after[..pos].ends_with(char::is_whitespace),
+                _ => true,
+            }
+            && !(delim == "_" && next.is_some_and(char::is_alphanumeric));
+        if valid {
+            return Some(pos);
+        }
+        from = pos + delim.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_markup() {
+        assert_eq!(
+            to_wikitext("**bold** *italic* _also_ __under__ ~~strike~~ ||secret||"),
+            "'''bold''' ''italic'' ''also'' <u>under</u> <s>strike</s> <span class=\"spoiler\">secret</span>"
+        );
+        assert_eq!(to_wikitext("***both***"), "'''''both'''''");
+        assert_eq!(
+            to_wikitext("*italic with **bold** inside*"),
+            "''italic with '''bold''' inside''"
+        );
+        assert_eq!(to_wikitext("snake_case_name"), "snake_case_name");
+        assert_eq!(to_wikitext("2 * 3 * 4"), "2 * 3 * 4");
+        assert_eq!(to_wikitext(r"not \*italic\*"), "not *italic*");
+        assert_eq!(
+            to_wikitext("`**code**` and [the docs](<https://example.com/a>)"),
+            "<code><nowiki>**code**</nowiki></code> and [https://example.com/a the docs]"
+        );
+        assert_eq!(to_wikitext("line one\nline two"), "line one<br />\nline two");
+        assert_eq!(
+            to_wikitext("~~~~ `</nowiki>{{x}}`"),
+            "<nowiki>~~~~</nowiki> <code><nowiki>&lt;/nowiki>{{x}}</nowiki></code>"
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let content = "# Title\nSome text\n## Section\n- one\n  - nested\n- two\n1. first\n2. second\n\n> quoted\n> **twice**\n```rust\nlet x = 1;\n```\n-# small";
+        assert_eq!(
+            to_wikitext(content),
+            "== Title ==\n\nSome text\n\n=== Section ===\n\n* one\n** nested\n* two\n\n# first\n# second\n\n<blockquote>quoted<br />\n'''twice'''</blockquote>\n\n<pre>let x = 1;</pre>\n\nsmall"
+        );
+        assert_eq!(
+            to_wikitext("before\n>>> all\nof this"),
+            "before\n\n<blockquote>all<br />\nof this</blockquote>"
+        );
+        // characters with a meaning at the start of a wikitext line
+        assert_eq!(to_wikitext("#general\n:)"), "<nowiki/>#general<br />\n<nowiki/>:)");
+    }
+}
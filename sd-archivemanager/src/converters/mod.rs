@@ -1,168 +1,519 @@
 #![allow(async_fn_in_trait)]
-use std::{collections::HashMap, sync::Arc};
+use std::{path::PathBuf, sync::Arc};
 
 use tokio::{
-    fs::{File, OpenOptions},
-    io::AsyncReadExt,
+    fs::OpenOptions,
+    io::AsyncWriteExt,
     sync::MutexGuard,
-    task,
+    task, try_join,
 };
+use url::Url;
 
 use crate::regex::Target;
-use chrono::NaiveDate;
-use cookie_store::CookieStore;
+use attachment::Attachment;
+use author::Author;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use colored::Colorize;
 use extism::{Manifest, Plugin, Wasm, convert::Json};
 use regex::Regex;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use snafu::{OptionExt, ResultExt, ensure_whatever};
-use xdg::BaseDirectories;
+use snafu::{OptionExt, ResultExt};
+use tinytemplate::{TinyTemplate, format_unescaped};
 
 use crate::{
+    CONFIG,
     config::Config,
-    error::{Error, ExtismSnafu, IoSnafu, ReqwestSnafu},
+    diff::{diff_lines, has_changes, render_diff},
+    error::{Error, ExtismSnafu, InvalidRegexSnafu, IoSnafu, ReqwestSnafu, TokioSnafu},
     guilds::GuildInfo,
+    ledger::{Ledger, LedgerEntry, content_hash},
     plugins::{PluginManager, PluginManifest, PluginStage},
+    mediawiki::{self, EditResult},
     regex::RegexManager,
+    store::{Record, Store},
 };
 
+pub mod attachment;
+pub mod author;
 pub mod case_law;
+pub mod category;
 pub mod eo;
+pub mod index;
 pub mod legislation;
+pub mod markdown;
+pub mod redirect;
 
-pub async fn get_token(
-    cfg: &MutexGuard<'_, Config>,
-    client: &Client,
-    _: &BaseDirectories,
-) -> Result<String, Error> {
-    let mut form = HashMap::new();
-    form.insert("action", "query");
-    form.insert("meta", "tokens");
-    form.insert("type", "login");
-    form.insert("format", "json");
-    let login = client
-        .get(&cfg.endpoint)
-        .query(&form)
-        .send()
-        .await
-        .context(ReqwestSnafu)?
-        .json::<Value>()
-        .await
-        .context(ReqwestSnafu)?["query"]["tokens"]["logintoken"]
-        .as_str()
-        .whatever_context("invalid response")?
-        .to_string();
-    let mut form = HashMap::new();
-    form.insert("format", "json");
-    form.insert("action", "clientlogin");
-    form.insert("loginreturnurl", &cfg.endpoint);
-    form.insert("logintoken", &login);
-    form.insert("username", &cfg.login);
-    form.insert("password", &cfg.passwd);
-    let login = client
-        .post(&cfg.endpoint)
-        .form(&form)
-        .send()
-        .await
-        .context(ReqwestSnafu)?
-        .json::<Value>()
-        .await
-        .context(ReqwestSnafu)?;
-    ensure_whatever!(
-        login["clientlogin"]["status"].as_str() == Some("PASS"),
-        "Login failed: {}",
-        &login
-    );
-    drop(login);
-    let mut form = HashMap::new();
-    form.insert("action", "query");
-    form.insert("meta", "tokens");
-    form.insert("format", "json");
-    let response = client
-        .get(&cfg.endpoint)
-        .query(&form)
-        .send()
-        .await
-        .context(ReqwestSnafu)?
-        .json::<Value>()
-        .await
-        .context(ReqwestSnafu)?;
-    ensure_whatever!(
-        response["query"]["tokens"]["csrftoken"].as_str() != Some(r#"+\\"#),
-        "failed to get token: {}",
-        &response
-    );
-    Ok(response["query"]["tokens"]["csrftoken"]
-        .as_str()
-        .unwrap()
-        .to_string())
+/// Metadata sent along with an edit
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EditParams {
+    pub summary: String,
+    pub bot: bool,
+    pub minor: bool,
+    pub tags: Vec<String>,
+    /// Timestamp of the revision the edit is based on, the edit fails with
+    /// [Error::EditConflict] if the page was changed after it
+    pub basetimestamp: Option<DateTime<Utc>>,
+    /// When the edit process started, the edit fails if the page was deleted after it
+    pub starttimestamp: Option<DateTime<Utc>>,
+    /// Fail with [Error::PageExists] instead of editing an existing page
+    pub createonly: bool,
+    /// Fail with [Error::PageMissing] instead of creating a page
+    pub nocreate: bool,
+}
+
+impl EditParams {
+    /// Builds the parameters for an edit to a `target` page from the config and `opts`.
+    /// The summary template is rendered with `page`.
+    pub fn new<T: Serialize>(
+        page: &T,
+        target: Target,
+        cfg: &Config,
+        opts: &UploadOptions,
+    ) -> Result<Self, Error> {
+        let template = opts
+            .summary
+            .as_deref()
+            .unwrap_or(cfg.edit.summary(target));
+        let mut templater = TinyTemplate::new();
+        templater.set_default_formatter(&format_unescaped);
+        templater
+            .add_template("summary", template)
+            .whatever_context("invalid summary template")?;
+        let mut tags = cfg.edit.tags.clone();
+        tags.extend(opts.tags.iter().cloned());
+        Ok(EditParams {
+            summary: templater
+                .render("summary", page)
+                .whatever_context("failed to render summary")?,
+            bot: cfg.edit.bot,
+            minor: cfg.edit.minor || opts.minor,
+            tags,
+            createonly: opts.mode == UploadMode::CreateOnly,
+            nocreate: opts.mode == UploadMode::UpdateOnly,
+            ..Default::default()
+        })
+    }
+}
+
+/// Asks on the terminal whether `title` should be overwritten
+async fn confirm_edit(title: &str, diff: &str) -> Result<bool, Error> {
+    let prompt = format!("{diff}Overwrite {}? [y/N] ", title.bold());
+    task::spawn_blocking(move || {
+        eprint!("{prompt}");
+        let mut answer = String::new();
+        std::io::stdin()
+            .read_line(&mut answer)
+            .map(|_| matches!(answer.trim(), "y" | "Y" | "yes"))
+    })
+    .await
+    .context(TokioSnafu)?
+    .context(IoSnafu { file: "<stdin>" })
+}
+
+/// Where rendered pages are sent to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Destination {
+    /// Log in and edit the page on the wiki
+    #[default]
+    Wiki,
+    /// Print the rendered wikitext, nothing is sent to the wiki
+    Stdout,
+    /// Append the rendered wikitext to a file, nothing is sent to the wiki
+    File(PathBuf),
+}
+
+/// Which pages an upload may touch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UploadMode {
+    /// Create the page or overwrite the existing one
+    #[default]
+    Overwrite,
+    /// Only create new pages, fail with [Error::PageExists] otherwise
+    CreateOnly,
+    /// Only update existing pages, fail with [Error::PageMissing] otherwise
+    UpdateOnly,
+}
+
+/// Options shared by every upload path
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UploadOptions {
+    pub destination: Destination,
+    /// Show the diff against the current revision and ask before overwriting a page
+    pub confirm: bool,
+    /// Skip documents whose rendered wikitext matches what the [Ledger] recorded for them
+    pub skip_unchanged: bool,
+    /// Edit summary template used instead of the configured one
+    pub summary: Option<String>,
+    /// Mark edits as minor, even if the config does not
+    pub minor: bool,
+    /// Change tags added on top of the configured ones
+    pub tags: Vec<String>,
+    pub mode: UploadMode,
+    /// Categories added on top of the guild defaults, see [category::categories]
+    pub categories: Vec<String>,
+    /// Leave the index page alone, set by batches that update it once after the last document
+    pub skip_index: bool,
+}
+
+/// What [publish] did with a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublishOutcome {
+    /// The page was edited
+    Edited(EditResult),
+    /// The latest revision already has the rendered content
+    Unchanged(u64),
+    /// The user declined to overwrite the page
+    Declined,
+    /// The page was written to a dry-run destination
+    DryRun,
+    /// The [Ledger] shows the same content was uploaded before
+    Skipped,
+}
+
+impl UploadOptions {
+    pub fn is_dry_run(&self) -> bool {
+        self.destination != Destination::Wiki
+    }
 }
-pub async fn upload<'a>(
-    name: &'a str,
-    client: &Client,
-    token: &'a str,
-    cfg: &'a MutexGuard<'a, Config>,
+
+/// Sends a rendered page to the destination in `opts`.
+/// Only [Destination::Wiki] logs in, the dry-run destinations prefix each page with its title
+/// as an HTML comment so several pages can share one output.
+/// Wiki edits are skipped when the page already has the rendered content, or when
+/// [UploadOptions::confirm] is set and the user declines.
+/// If the page is still at `previous` and `title` does not exist yet, it is compared against
+/// and moved to `title` once the mode check and the confirmation passed.
+/// `attachments` are mirrored right before the page is edited.
+/// The edit is based on the revision fetched for the diff, so it fails with
+/// [Error::EditConflict] if someone else edits the page in the meantime.
+pub async fn publish(
+    title: &str,
+    previous: Option<&str>,
     content: &str,
-) -> Result<(), Error> {
-    let mut form = HashMap::new();
-    form.insert("action", "edit");
-    form.insert("title", name);
-    form.insert("token", token);
-    form.insert("format", "json");
-    form.insert("text", &content);
-    form.insert("assertuser", &cfg.login);
-    client
-        .post(&cfg.endpoint)
-        .form(&form)
-        .send()
-        .await
-        .context(ReqwestSnafu)?;
-    Ok(())
+    attachments: &[Attachment],
+    params: &EditParams,
+    opts: &UploadOptions,
+    cfg: &MutexGuard<'_, Config>,
+) -> Result<PublishOutcome, Error> {
+    let outcome = match &opts.destination {
+        Destination::Wiki => {
+            let mut wiki = mediawiki::shared(cfg).await?;
+            let mut started = Utc::now();
+            let mut current = wiki.page(title).await?;
+            let mut moving = None;
+            if let (None, Some(previous)) = (&current, previous) {
+                current = wiki.page(previous).await?;
+                moving = current.is_some().then_some(previous);
+            }
+            match (&current, opts.mode) {
+                (Some(_), UploadMode::CreateOnly) => {
+                    return Err(Error::PageExists {
+                        title: title.to_string(),
+                    });
+                }
+                (None, UploadMode::UpdateOnly) => {
+                    return Err(Error::PageMissing {
+                        title: title.to_string(),
+                    });
+                }
+                _ => {}
+            }
+            let diff = diff_lines(
+                current.as_ref().map_or("", |rev| rev.content.as_str()),
+                content,
+            );
+            let changed = has_changes(&diff);
+            if current.is_some()
+                && changed
+                && opts.confirm
+                && !confirm_edit(title, &render_diff(&diff, 3)).await?
+            {
+                wiki.save().await?;
+                return Ok(PublishOutcome::Declined);
+            }
+            if let Some(previous) = moving {
+                let reason = format!("Title changed to [[{title}]]");
+                if wiki.move_page(previous, title, &reason).await? {
+                    eprintln!("Moved {} to {}", previous.bold(), title.bold());
+                }
+                // the move added a revision, the edit is based on that one
+                started = Utc::now();
+                current = wiki.page(title).await?;
+            }
+            let params = &EditParams {
+                basetimestamp: current.as_ref().map(|rev| rev.timestamp),
+                starttimestamp: Some(started),
+                // a page created by someone else after the fetch is a conflict as well
+                createonly: params.createonly || current.is_none(),
+                ..params.clone()
+            };
+            let outcome = match &current {
+                Some(rev) if !changed => {
+                    eprintln!("{} is up to date, skipping", title.bold());
+                    PublishOutcome::Unchanged(rev.revid)
+                }
+                _ => {
+                    attachment::mirror(attachments, &mut wiki, cfg).await?;
+                    PublishOutcome::Edited(wiki.edit(title, content, params).await?)
+                }
+            };
+            wiki.save().await?;
+            outcome
+        }
+        Destination::Stdout => {
+            let mut stdout = tokio::io::stdout();
+            stdout
+                .write_all(format!("<!-- {title} -->\n{content}\n").as_bytes())
+                .await
+                .context(IoSnafu { file: "<stdout>" })?;
+            stdout.flush().await.context(IoSnafu { file: "<stdout>" })?;
+            PublishOutcome::DryRun
+        }
+        Destination::File(path) => {
+            OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .await
+                .context(IoSnafu { file: path })?
+                .write_all(format!("<!-- {title} -->\n{content}\n").as_bytes())
+                .await
+                .context(IoSnafu { file: path })?;
+            PublishOutcome::DryRun
+        }
+    };
+    Ok(outcome)
+}
+
+/// Template engine with `template` added as `template`, used by every target.
+/// Values are inserted unescaped since the pages are wikitext and not HTML.
+pub(crate) fn templater(template: &str) -> Result<TinyTemplate<'_>, Error> {
+    let mut templater = TinyTemplate::new();
+    templater.set_default_formatter(&format_unescaped);
+    templater
+        .add_template("template", template)
+        .whatever_context("invalid template")?;
+    Ok(templater)
+}
+
+/// Formats a scraped page, renders it with the `template` template of `templater`
+/// followed by its categories, saves the result to the local [Store] and publishes it
+/// according to `opts`. Pages that end up on the wiki are recorded in the [Ledger],
+/// then get redirects from their aliases, their missing categories and a row on the guild's
+/// index page. Those follow-ups only print their failures, the page stays archived.
+/// If the title changed since the last upload, [publish] moves the old page to the new title.
+pub async fn archive<'a, T: PageData<'a>>(
+    raw: T,
+    id: u64,
+    guild: &GuildInfo,
+    target: Target,
+    scraped_at: DateTime<Utc>,
+    templater: &TinyTemplate<'_>,
+    opts: &UploadOptions,
+) -> Result<PublishOutcome, Error> {
+    let cfg = CONFIG.lock().await;
+    let mut formatted = raw.clone();
+    formatted.format(target, &cfg).await?;
+    let rendered = templater
+        .render("template", &formatted)
+        .whatever_context("failed to render")?;
+    let categories = category::categories(&formatted, guild, target, &opts.categories);
+    let rendered = category::append(&rendered, &categories);
+    let record = Record {
+        guild: guild.id,
+        id,
+        target,
+        raw,
+        formatted,
+        rendered,
+        scraped_at,
+    };
+    Store::open()?.save(&record).await?;
+    let title = record.formatted.get_title();
+    let hash = content_hash(&record.rendered);
+    let mut ledger = Ledger::load().await?;
+    if opts.skip_unchanged
+        && !opts.is_dry_run()
+        && ledger
+            .get(guild.id, target, id)
+            .is_some_and(|e| e.hash == hash && e.title == title)
+    {
+        return Ok(PublishOutcome::Skipped);
+    }
+    let params = EditParams::new(&record.formatted, target, &cfg, opts)?;
+    // the thread was renamed or the title profiles changed since the last upload
+    let previous = ledger
+        .get(guild.id, target, id)
+        .map(|e| e.title.as_str())
+        .filter(|previous| *previous != title);
+    let outcome = publish(
+        title,
+        previous,
+        &record.rendered,
+        record.formatted.get_attachments(),
+        &params,
+        opts,
+        &cfg,
+    )
+    .await?;
+    let revid = match &outcome {
+        PublishOutcome::Edited(edit) => edit.newrevid.or(edit.oldrevid),
+        PublishOutcome::Unchanged(revid) => Some(*revid),
+        _ => return Ok(outcome),
+    };
+    ledger.record(LedgerEntry {
+        guild: guild.id,
+        target,
+        id,
+        title: title.to_string(),
+        revid,
+        hash,
+        uploaded_at: Utc::now(),
+    });
+    ledger.save().await?;
+    // the page is archived, failures past this point are reported without failing it
+    if let Err(err) = category::create_missing(&categories, &cfg).await {
+        eprintln!("Failed to create the categories of {}: {err}", title.bold());
+    }
+    let rgx = RegexManager::load().await.unwrap_or_else(|_| RegexManager::new());
+    let redirects = async {
+        let aliases = redirect::aliases(&record.formatted, &rgx, target)?;
+        redirect::create_redirects(title, &aliases, &cfg).await
+    };
+    if let Err(err) = redirects.await {
+        eprintln!("Failed to create the redirects to {}: {err}", title.bold());
+    }
+    if !opts.skip_index {
+        if let Err(err) = index::update_index(guild, target, opts, &cfg).await {
+            eprintln!("Failed to update the {} index: {err}", target.name());
+        }
+    }
+    Ok(outcome)
+}
+
+/// Runs formatting and rendering again on stored scrapes, without contacting discord.
+/// With `id` set only that message is re-rendered, otherwise every stored record of `target`.
+/// Failures are returned with the message ID, see [archive_each].
+pub async fn rerender<'a, T: PageData<'a>>(
+    id: Option<u64>,
+    guild: &GuildInfo,
+    target: Target,
+    templater: &TinyTemplate<'_>,
+    opts: &UploadOptions,
+) -> Result<Vec<(u64, Error)>, Error> {
+    let store = Store::open()?;
+    let ids = match id {
+        Some(id) => vec![id],
+        None => store.list(guild.id, target).await?,
+    };
+    let failed = archive_each(ids, guild, target, opts, async |id, opts| {
+        let record = store
+            .load::<T>(guild.id, target, id)
+            .await?
+            .whatever_context(format!("{} {id} was never archived", target.name()))?;
+        archive(record.raw, id, guild, target, record.scraped_at, templater, opts).await
+    })
+    .await;
+    Ok(failed)
 }
 
-async fn get_cookies(
-    xdg: &BaseDirectories,
-) -> Result<(File, Arc<reqwest_cookie_store::CookieStoreMutex>), Error> {
-    let cookie_file = xdg.place_data_file("cookies.json").unwrap();
-    if cookie_file.is_file() {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .read(true)
-            .open(&cookie_file)
-            .await
-            .context(IoSnafu {
-                file: cookie_file.to_string_lossy().to_string(),
-            })?;
-        let mut buf = vec![];
-        let cookie_store = CookieStore::load(buf.as_slice(), |s| serde_json::from_str(s))
-            .expect("cookies are broken");
-        file.read_to_end(&mut buf).await.context(IoSnafu {
-            file: cookie_file.to_string_lossy().to_string(),
-        })?;
-        Ok((
-            file,
-            Arc::new(reqwest_cookie_store::CookieStoreMutex::new(cookie_store)),
-        ))
-    } else {
-        Ok((
-            File::create_new(&cookie_file).await.unwrap(),
-            Arc::new(reqwest_cookie_store::CookieStoreMutex::new(
-                CookieStore::default(),
-            )),
-        ))
+/// Archives every ID in `ids` with `archive_id`, then updates the index page of `target` once.
+/// A failing ID does not stop the others, failures are returned with the ID instead.
+pub(crate) async fn archive_each<F>(
+    ids: Vec<u64>,
+    guild: &GuildInfo,
+    target: Target,
+    opts: &UploadOptions,
+    mut archive_id: F,
+) -> Vec<(u64, Error)>
+where
+    F: AsyncFnMut(u64, &UploadOptions) -> Result<PublishOutcome, Error>,
+{
+    let batch = UploadOptions {
+        skip_index: true,
+        ..opts.clone()
+    };
+    let mut failed = Vec::new();
+    for id in ids {
+        if let Err(err) = archive_id(id, &batch).await {
+            failed.push((id, err));
+        }
+    }
+    if let Err(err) = index::rebuild(guild, target, opts).await {
+        eprintln!("Failed to update the {} index: {err}", target.name());
     }
+    failed
+}
+
+/// Milliseconds between the unix epoch and the discord epoch (2015-01-01)
+const DISCORD_EPOCH: u64 = 1420070400000;
+
+/// Date a discord snowflake (message, thread, channel ID) was created on
+pub(crate) fn snowflake_date(id: u64) -> NaiveDate {
+    DateTime::from_timestamp_millis(((id >> 22) + DISCORD_EPOCH) as i64)
+        .unwrap_or_default()
+        .date_naive()
+}
+
+/// Smallest discord snowflake created on `date`, usable as a `before`/`after` cursor
+pub(crate) fn date_snowflake(date: NaiveDate) -> u64 {
+    let millis = date.and_time(NaiveTime::MIN).and_utc().timestamp_millis() as u64;
+    millis.saturating_sub(DISCORD_EPOCH) << 22
+}
+
+/// Google Doc link in a message, other links like a debate thread, a Sheet or a Form are skipped
+pub(super) const GDOC_LINK: &str = r"https://docs\.google\.com/document/[^])>\s]+";
+
+/// Fetches a Google Doc as markdown together with its title.
+/// `url` has to be a document link containing a `/d/<id>` segment.
+pub(crate) async fn fetch_gdoc(client: &Client, url: &Url) -> Result<(String, String), Error> {
+    let mut segments = url.path_segments().whatever_context("invalid document link")?;
+    segments
+        .position(|x| x == "d")
+        .whatever_context("invalid document link")?;
+    let id = segments.next().whatever_context("invalid document link")?;
+    let (content, title) = try_join! {client
+    .get(format!(
+        "https://docs.google.com/document/d/{}/export?format=md",
+        id
+    ))
+    .send(), client
+        .get(format!("https://docs.google.com/document/d/{}/view", id))
+        .send()}
+    .context(ReqwestSnafu)?;
+    let (content, title) = try_join!(content.text(), title.text()).context(ReqwestSnafu)?;
+    let re = Regex::new(r"<title>(.*?)</title>").context(InvalidRegexSnafu)?;
+    let name = match re.captures(&title) {
+        Some(captures) => {
+            let raw_title = captures.get(1).map_or("", |m| m.as_str());
+            raw_title
+                .strip_suffix(" - Google Docs")
+                .unwrap_or(raw_title)
+                .to_string()
+        }
+        None => "".to_string(),
+    };
+    Ok((name, content))
 }
 
 pub trait PageData<'a>: Serialize + for<'de> Deserialize<'de> + Clone {
     fn get_content(&self) -> &str;
     fn get_title(&self) -> &str;
     fn get_date(&self) -> &NaiveDate;
-    fn get_author(&self) -> &str;
+    fn get_author(&self) -> &Author;
     fn get_mut_content(&mut self) -> &mut String;
     fn get_mut_title(&mut self) -> &mut String;
     fn get_mut_date(&mut self) -> &mut NaiveDate;
-    fn get_mut_author(&mut self) -> &mut String;
+    fn get_mut_author(&mut self) -> &mut Author;
+    /// Files attached to the source message, mirrored to the wiki by [publish] before the page is edited
+    fn get_attachments(&self) -> &[Attachment] {
+        &[]
+    }
+    /// Whether the content is Discord markdown, converted to wikitext by [PageData::format]
+    fn is_discord_markdown(&self) -> bool {
+        false
+    }
     async fn scrape(
         id: u64,
         conf: Option<&MutexGuard<'_, Config>>,
@@ -207,7 +558,7 @@ pub trait PageData<'a>: Serialize + for<'de> Deserialize<'de> + Clone {
         Ok(())
     }
     fn format_rgx(&mut self, rgx: &RegexManager, target: Target) {
-        let patterns = rgx.get_regexs(&self.get_author(), target);
+        let patterns = rgx.get_regexs(&self.get_author().username, target);
         for pat in patterns {
             let reg = Regex::new(&pat.regex).unwrap();
             if pat.for_title {
@@ -229,8 +580,12 @@ pub trait PageData<'a>: Serialize + for<'de> Deserialize<'de> + Clone {
         let rgx = task::spawn(async move { RegexManager::load() });
         let rgx = rgx.await.unwrap().await?;
         let plugin_manager = plugin_manager.await.unwrap().await.unwrap();
-        let plugins = plugin_manager.get_plugins(self.get_author(), target);
+        self.get_mut_author().map_wiki_user(&cfg.wiki_users);
+        let plugins = plugin_manager.get_plugins(&self.get_author().username, target);
         self.format_plugins(&plugins, PluginStage::Pre, cfg).await?;
+        if self.is_discord_markdown() {
+            *self.get_mut_content() = markdown::to_wikitext(self.get_content());
+        }
         self.format_rgx(&rgx, target);
         self.format_plugins(&plugins, PluginStage::Post, cfg)
             .await?;
@@ -240,19 +595,38 @@ pub trait PageData<'a>: Serialize + for<'de> Deserialize<'de> + Clone {
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
-
-    use crate::CONFIG;
-
     use super::*;
 
-    #[tokio::test]
-    async fn test_get_login() {
-        let client = Client::builder().cookie_store(true).build().unwrap();
-        let xdg = BaseDirectories::with_prefix("sd-archivemanager").unwrap();
-        let _ = get_token(&CONFIG.lock().await, &client, &xdg)
-            .await
-            .unwrap();
-        fs::remove_file(xdg.place_data_file("cookies.json").unwrap()).unwrap();
+    #[test]
+    fn edit_params() {
+        let mut cfg = Config::for_tests();
+        cfg.edit.tags = vec!["archive".to_string()];
+        let page = serde_json::json!({"name": "EO 12 | Archives", "author": "f3rri5_"});
+        let params = EditParams::new(&page, Target::EO, &cfg, &UploadOptions::default()).unwrap();
+        assert_eq!(params.summary, "Archive EO EO 12 | Archives by f3rri5_");
+        assert!(!params.minor);
+        let opts = UploadOptions {
+            summary: Some("Fix {name}".to_string()),
+            minor: true,
+            tags: vec!["correction".to_string()],
+            ..Default::default()
+        };
+        let params = EditParams::new(&page, Target::EO, &cfg, &opts).unwrap();
+        assert_eq!(params.summary, "Fix EO 12 | Archives");
+        assert!(params.minor);
+        assert_eq!(params.tags, vec!["archive", "correction"]);
+    }
+    #[test]
+    fn snowflake_to_date() {
+        assert_eq!(
+            snowflake_date(1348749282349416529),
+            NaiveDate::from_ymd_opt(2025, 3, 10).unwrap()
+        );
+        let date = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        assert_eq!(snowflake_date(date_snowflake(date)), date);
+        assert_eq!(
+            snowflake_date(date_snowflake(date) - 1),
+            date.pred_opt().unwrap()
+        );
     }
 }
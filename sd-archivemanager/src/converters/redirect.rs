@@ -0,0 +1,132 @@
+use colored::Colorize;
+use regex::Regex;
+use snafu::ResultExt;
+use tokio::sync::MutexGuard;
+
+use crate::{
+    config::Config,
+    error::{Error, InvalidRegexSnafu},
+    mediawiki,
+    regex::{RegexManager, Target},
+};
+
+use super::{EditParams, PageData};
+
+/// Alternate titles of a document derived from the alias rules of `rgx`.
+/// Rules that do not match are skipped, the document's own title is never an alias.
+pub fn aliases<'a, T: PageData<'a>>(
+    doc: &T,
+    rgx: &RegexManager,
+    target: Target,
+) -> Result<Vec<String>, Error> {
+    let mut aliases = Vec::<String>::new();
+    for rule in rgx.get_aliases(&doc.get_author().username, target) {
+        let reg = Regex::new(&rule.regex).context(InvalidRegexSnafu)?;
+        let haystack = if rule.for_title {
+            doc.get_title()
+        } else {
+            doc.get_content()
+        };
+        let Some(caps) = reg.captures(haystack) else {
+            continue;
+        };
+        let mut alias = String::new();
+        caps.expand(&rule.replace, &mut alias);
+        let alias = alias.trim();
+        if !alias.is_empty() && alias != doc.get_title() && !aliases.iter().any(|a| a == alias) {
+            aliases.push(alias.to_string());
+        }
+    }
+    Ok(aliases)
+}
+
+/// Page a redirect points to, [None] if `content` is not a redirect
+fn redirect_target(content: &str) -> Option<&str> {
+    let re = Regex::new(r"(?i)^\s*#REDIRECT\s*:?\s*\[\[([^\]|#]+)").unwrap();
+    re.captures(content)
+        .map(|caps| caps.get(1).unwrap().as_str().trim())
+}
+
+/// Points every alias at `title`. Missing aliases are created and redirects to other pages
+/// are updated, aliases holding real content are left alone.
+pub async fn create_redirects(
+    title: &str,
+    aliases: &[String],
+    cfg: &MutexGuard<'_, Config>,
+) -> Result<(), Error> {
+    if aliases.is_empty() {
+        return Ok(());
+    }
+    let mut wiki = mediawiki::shared(cfg).await?;
+    for alias in aliases {
+        let current = wiki.page(alias).await?;
+        let mut params = EditParams {
+            summary: format!("Redirect to [[{title}]]"),
+            bot: cfg.edit.bot,
+            ..Default::default()
+        };
+        match &current {
+            None => params.createonly = true,
+            Some(rev) => match redirect_target(&rev.content) {
+                Some(target) if target == title => continue,
+                Some(_) => params.basetimestamp = Some(rev.timestamp),
+                None => {
+                    eprintln!("{} has content, not redirecting it to {title}", alias.bold());
+                    continue;
+                }
+            },
+        }
+        wiki.edit(alias, &format!("#REDIRECT [[{title}]]"), &params)
+            .await?;
+    }
+    wiki.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{converters::legislation::LawInfo, regex::Profile};
+
+    #[test]
+    fn derive_aliases() {
+        let rule = |regex: &str, for_title, replace: &str| Profile {
+            author: "*".to_string(),
+            regex: regex.to_string(),
+            for_title,
+            name: replace.to_string(),
+            replace: replace.to_string(),
+            target: Target::Legislation,
+        };
+        let rgx = RegexManager {
+            profile: None,
+            alias: Some(vec![
+                rule(r"Act (\d+)", true, "Law $1"),
+                rule(r"Act (\d+)", true, "Public Act $1"),
+                rule(r"Bill (\d+)", false, "Bill $1"),
+                rule(r"Resolution (\d+)", true, "Resolution $1"),
+                rule(r".*", true, "$0"),
+            ]),
+        };
+        let law = LawInfo {
+            author: "f3rri5_".to_string().into(),
+            date: NaiveDate::from_ymd_opt(2025, 3, 10).unwrap(),
+            name: "Act 12".to_string(),
+            content: "Passed as Bill 40.".to_string(),
+            link: String::new(),
+            attachments: vec![],
+        };
+        assert_eq!(
+            aliases(&law, &rgx, Target::Legislation).unwrap(),
+            vec!["Bill 40", "Law 12", "Public Act 12"]
+        );
+        assert!(aliases(&law, &rgx, Target::EO).unwrap().is_empty());
+    }
+    #[test]
+    fn parse_redirect() {
+        assert_eq!(redirect_target("#REDIRECT [[EO 12]]"), Some("EO 12"));
+        assert_eq!(redirect_target("#redirect:[[EO 12#Text|x]]"), Some("EO 12"));
+        assert_eq!(redirect_target("An article"), None);
+    }
+}
@@ -0,0 +1,156 @@
+use colored::Colorize;
+
+/// One line of a line-level diff between two texts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+/// Largest LCS table [diff_lines] builds, about 32 MB. Longer changed ranges are shown as
+/// removed and added as a whole instead.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Computes a line-level diff turning `old` into `new`.
+/// Common leading and trailing lines are stripped before running the LCS table,
+/// so small edits on long pages stay cheap.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let mut diff = old[..prefix]
+        .iter()
+        .map(|l| DiffLine::Same(l))
+        .collect::<Vec<_>>();
+    if (a.len() + 1).saturating_mul(b.len() + 1) > MAX_LCS_CELLS {
+        diff.extend(a.iter().map(|l| DiffLine::Removed(l)));
+        diff.extend(b.iter().map(|l| DiffLine::Added(l)));
+    } else {
+        diff.extend(lcs_diff(a, b));
+    }
+    diff.extend(old[old.len() - suffix..].iter().map(|l| DiffLine::Same(l)));
+    diff
+}
+
+/// Diff of `a` and `b` keeping their longest common subsequence
+fn lcs_diff<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<DiffLine<'a>> {
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            diff.push(DiffLine::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(DiffLine::Removed(a[i]));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(b[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+/// Whether a diff contains any change
+pub fn has_changes(diff: &[DiffLine]) -> bool {
+    diff.iter().any(|l| !matches!(l, DiffLine::Same(_)))
+}
+
+/// Renders a diff in unified style with `context` unchanged lines around each change
+pub fn render_diff(diff: &[DiffLine], context: usize) -> String {
+    let near_change = |idx: usize| {
+        diff[idx.saturating_sub(context)..(idx + context + 1).min(diff.len())]
+            .iter()
+            .any(|l| !matches!(l, DiffLine::Same(_)))
+    };
+    let mut out = String::new();
+    let mut skipped = false;
+    for (idx, line) in diff.iter().enumerate() {
+        match line {
+            DiffLine::Same(l) if near_change(idx) => {
+                skipped = false;
+                out.push_str(&format!("  {l}\n"));
+            }
+            DiffLine::Same(_) => {
+                if !skipped {
+                    out.push_str(&format!("{}\n", "  ...".dimmed()));
+                }
+                skipped = true;
+            }
+            DiffLine::Added(l) => {
+                skipped = false;
+                out.push_str(&format!("{}\n", format!("+ {l}").green()));
+            }
+            DiffLine::Removed(l) => {
+                skipped = false;
+                out.push_str(&format!("{}\n", format!("- {l}").red()));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiffLine::*;
+
+    #[test]
+    fn diff_edit() {
+        assert_eq!(
+            diff_lines("a\nb\nc\nd", "a\nx\nc\nd\ne"),
+            vec![Same("a"), Removed("b"), Added("x"), Same("c"), Same("d"), Added("e")]
+        );
+    }
+    #[test]
+    fn diff_unchanged() {
+        let diff = diff_lines("a\nb", "a\nb");
+        assert_eq!(diff, vec![Same("a"), Same("b")]);
+        assert!(!has_changes(&diff));
+        assert!(has_changes(&diff_lines("", "a")));
+    }
+    #[test]
+    fn diff_large() {
+        let page = |name: &str| {
+            let lines = (0..3000)
+                .map(|i| format!("{name} {i}\n"))
+                .collect::<String>();
+            format!("a\n{lines}z")
+        };
+        let (old, new) = (page("old"), page("new"));
+        let diff = diff_lines(&old, &new);
+        assert_eq!(diff.len(), 6002);
+        assert_eq!(diff[1], Removed("old 0"));
+        assert_eq!(diff[3001], Added("new 0"));
+        assert_eq!(diff[6001], Same("z"));
+    }
+    #[test]
+    fn render_context() {
+        colored::control::set_override(false);
+        let diff = diff_lines("1\n2\n3\n4\n5\n6", "1\n2\n3\n4\n5\nx");
+        assert_eq!(render_diff(&diff, 1), "  ...\n  5\n- 6\n+ x\n");
+    }
+}
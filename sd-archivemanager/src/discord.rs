@@ -0,0 +1,640 @@
+use std::{collections::HashMap, str::FromStr, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use once_cell::sync::OnceCell;
+use reqwest::{Client, Method, Response, StatusCode, header::HeaderMap};
+use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+use snafu::{ResultExt, whatever};
+use tokio::time::{Instant, sleep_until};
+use url::Url;
+
+use crate::{
+    config::Config,
+    error::{Error, ReqwestSnafu},
+    guilds::GuildInfo,
+    regex::Target,
+};
+
+const API: &str = "https://discord.com/api/v10";
+
+/// 429s in a row after which a request is given up
+const MAX_RATE_LIMITED: u32 = 5;
+
+/// Discord client shared by every scrape of the process, so rate limits are tracked across them
+static DISCORD: OnceCell<DiscordClient> = OnceCell::new();
+
+/// The shared [DiscordClient], created with the token of `cfg` on first use
+pub fn shared(cfg: &Config) -> &'static DiscordClient {
+    DISCORD.get_or_init(|| DiscordClient::new(cfg))
+}
+
+fn snowflake<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+fn optional_snowflake<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|id| id.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// A discord user
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct User {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    pub username: String,
+    /// Display name, if the user set one
+    #[serde(default)]
+    pub global_name: Option<String>,
+    #[serde(default)]
+    pub bot: bool,
+}
+
+/// A user's membership in a guild
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Member {
+    pub user: User,
+    /// Guild specific display name
+    #[serde(default)]
+    pub nick: Option<String>,
+}
+
+/// A guild role
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Role {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    pub name: String,
+}
+
+/// A file attached to a message
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Attachment {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    pub filename: String,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Size in bytes
+    pub size: u64,
+    /// CDN URL of the file
+    pub url: String,
+}
+
+/// A message in a channel or thread
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Message {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    #[serde(deserialize_with = "snowflake")]
+    pub channel_id: u64,
+    pub author: User,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub edited_timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+/// Thread specific fields of a [Channel]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ThreadMetadata {
+    pub archived: bool,
+    /// When the thread was last archived or unarchived
+    pub archive_timestamp: DateTime<Utc>,
+}
+
+/// A guild channel or thread
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Channel {
+    #[serde(deserialize_with = "snowflake")]
+    pub id: u64,
+    /// Channel type, see the discord documentation
+    #[serde(rename = "type")]
+    pub kind: u8,
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub guild_id: Option<u64>,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Category of a channel, or the channel a thread was started in
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub parent_id: Option<u64>,
+    /// Creator of a thread
+    #[serde(default, deserialize_with = "optional_snowflake")]
+    pub owner_id: Option<u64>,
+    /// Set for threads only
+    #[serde(default)]
+    pub thread_metadata: Option<ThreadMetadata>,
+}
+
+/// A page of threads
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ThreadList {
+    pub threads: Vec<Channel>,
+    /// Whether older archived threads can be fetched with a `before` cursor
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+/// A parsed `https://discord.com/channels/...` link, `ptb.` and `canary.` hosts included.
+/// The guild is [None] for direct messages (`@me`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscordLink {
+    Guild(u64),
+    /// A channel, or a thread linked by its own ID
+    Channel { guild: Option<u64>, channel: u64 },
+    /// A thread linked through the channel it was started in
+    Thread {
+        guild: Option<u64>,
+        parent: u64,
+        thread: u64,
+    },
+    Message {
+        guild: Option<u64>,
+        channel: u64,
+        message: u64,
+    },
+}
+
+impl DiscordLink {
+    pub fn guild(&self) -> Option<u64> {
+        match *self {
+            DiscordLink::Guild(guild) => Some(guild),
+            DiscordLink::Channel { guild, .. }
+            | DiscordLink::Thread { guild, .. }
+            | DiscordLink::Message { guild, .. } => guild,
+        }
+    }
+}
+
+impl FromStr for DiscordLink {
+    type Err = Error;
+
+    fn from_str(link: &str) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::InvalidLink {
+            link: link.to_string(),
+            reason: reason.to_string(),
+        };
+        let url = Url::parse(link.trim()).map_err(|err| invalid(&err.to_string()))?;
+        let host = url.host_str().unwrap_or_default();
+        let host = host.strip_prefix("www.").unwrap_or(host);
+        let host = ["ptb.", "canary."]
+            .iter()
+            .find_map(|prefix| host.strip_prefix(prefix))
+            .unwrap_or(host);
+        if !matches!(host, "discord.com" | "discordapp.com") {
+            return Err(invalid("not a discord link"));
+        }
+        let segments = url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        let Some((&"channels", segments)) = segments.split_first() else {
+            return Err(invalid("not a channel or message link"));
+        };
+        let id = |segment: &str| {
+            segment
+                .parse::<u64>()
+                .map_err(|_| invalid(&format!("{segment} is not an ID")))
+        };
+        let guild = match segments.first() {
+            Some(&"@me") => None,
+            Some(guild) => Some(id(guild)?),
+            None => return Err(invalid("missing guild")),
+        };
+        Ok(match segments[1..] {
+            [] => DiscordLink::Guild(guild.ok_or_else(|| invalid("missing channel"))?),
+            [channel] => DiscordLink::Channel {
+                guild,
+                channel: id(channel)?,
+            },
+            [parent, "threads", thread] => DiscordLink::Thread {
+                guild,
+                parent: id(parent)?,
+                thread: id(thread)?,
+            },
+            [channel, message] => DiscordLink::Message {
+                guild,
+                channel: id(channel)?,
+                message: id(message)?,
+            },
+            _ => return Err(invalid("unknown link format")),
+        })
+    }
+}
+
+/// Message ID of the `target` document `input` points to, `input` being a raw ID or a link.
+/// Links have to be in `guild` and point into its source channel for `target`: an EO is
+/// identified by its thread, laws and verdicts by their message.
+pub fn document_id(input: &str, guild: &GuildInfo, target: Target) -> Result<u64, Error> {
+    if let Ok(id) = input.trim().parse() {
+        return Ok(id);
+    }
+    let link = input.parse::<DiscordLink>()?;
+    let invalid = |reason: String| Error::InvalidLink {
+        link: input.to_string(),
+        reason,
+    };
+    if link.guild() != Some(guild.id) {
+        return Err(invalid(format!("it does not point into {}", guild.name)));
+    }
+    let source = match target {
+        Target::EO => guild.eo_src,
+        Target::Legislation => guild.leg_src,
+        Target::CaseLaw => guild.case_src,
+    };
+    match (target, link) {
+        // the thread itself, or a message in it; the thread's parent is checked when scraping
+        (Target::EO, DiscordLink::Channel { channel, .. })
+        | (Target::EO, DiscordLink::Message { channel, .. }) => Ok(channel),
+        (Target::EO, DiscordLink::Thread { parent, thread, .. }) if parent == source => Ok(thread),
+        (_, DiscordLink::Message {
+            channel, message, ..
+        }) if channel == source => Ok(message),
+        (_, DiscordLink::Thread { .. } | DiscordLink::Message { .. }) => Err(invalid(format!(
+            "it is not in the {} channel of {}",
+            target.name(),
+            guild.name
+        ))),
+        _ => Err(invalid(format!("it does not point to a {} document", target.name()))),
+    }
+}
+
+/// Rate limit state of a bucket as of the last response
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    remaining: u32,
+    reset: Instant,
+}
+
+/// Client for the discord REST API.
+/// Rate limits are tracked per bucket as announced in the `X-RateLimit-*` headers,
+/// requests wait for an exhausted bucket to reset and 429s are retried after `retry_after`.
+#[derive(Debug)]
+pub struct DiscordClient {
+    http: Client,
+    auth: String,
+    /// Bucket hash per route, routes are only learned from responses
+    routes: Mutex<HashMap<String, String>>,
+    /// State per bucket hash and major parameter
+    buckets: Mutex<HashMap<String, Bucket>>,
+    /// Until when every request is blocked by the global rate limit
+    global: Mutex<Option<Instant>>,
+}
+
+impl DiscordClient {
+    /// Client authenticated with [Config::token], as a bot if [Config::bot] is set
+    pub fn new(cfg: &Config) -> Self {
+        let auth = if cfg.bot {
+            format!("Bot {}", cfg.token)
+        } else {
+            cfg.token.clone()
+        };
+        DiscordClient {
+            http: Client::new(),
+            auth,
+            routes: Mutex::default(),
+            buckets: Mutex::default(),
+            global: Mutex::default(),
+        }
+    }
+
+    pub async fn message(&self, channel: u64, id: u64) -> Result<Message, Error> {
+        self.get(&format!("/channels/{channel}/messages/{id}"), &[])
+            .await
+    }
+    /// Up to `limit` (at most 100) messages of `channel` older than `before`, newest first
+    pub async fn messages(
+        &self,
+        channel: u64,
+        before: Option<u64>,
+        limit: u8,
+    ) -> Result<Vec<Message>, Error> {
+        let mut query = vec![("limit", limit.min(100).to_string())];
+        if let Some(before) = before {
+            query.push(("before", before.to_string()));
+        }
+        self.get(&format!("/channels/{channel}/messages"), &query)
+            .await
+    }
+    /// Every message of `channel` posted after the message `after`, oldest first
+    pub async fn messages_after(&self, channel: u64, after: u64) -> Result<Vec<Message>, Error> {
+        let mut messages = Vec::new();
+        let mut after = after;
+        loop {
+            let query = [("limit", "100".to_string()), ("after", after.to_string())];
+            let mut page = self
+                .get::<Vec<Message>>(&format!("/channels/{channel}/messages"), &query)
+                .await?;
+            page.sort_by_key(|message| message.id);
+            let full = page.len() == 100;
+            let Some(last) = page.last() else {
+                break;
+            };
+            after = last.id;
+            messages.append(&mut page);
+            if !full {
+                break;
+            }
+        }
+        Ok(messages)
+    }
+    pub async fn channel(&self, id: u64) -> Result<Channel, Error> {
+        self.get(&format!("/channels/{id}"), &[]).await
+    }
+    pub async fn user(&self, id: u64) -> Result<User, Error> {
+        self.get(&format!("/users/{id}"), &[]).await
+    }
+    pub async fn member(&self, guild: u64, user: u64) -> Result<Member, Error> {
+        self.get(&format!("/guilds/{guild}/members/{user}"), &[])
+            .await
+    }
+    pub async fn roles(&self, guild: u64) -> Result<Vec<Role>, Error> {
+        self.get(&format!("/guilds/{guild}/roles"), &[]).await
+    }
+    /// Every active thread of the guild, regardless of the channel it is in
+    pub async fn active_threads(&self, guild: u64) -> Result<ThreadList, Error> {
+        self.get(&format!("/guilds/{guild}/threads/active"), &[])
+            .await
+    }
+    /// Public archived threads of `channel` archived before `before`, most recently archived first
+    pub async fn archived_threads(
+        &self,
+        channel: u64,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<ThreadList, Error> {
+        let mut query = vec![("limit", "100".to_string())];
+        if let Some(before) = before {
+            query.push(("before", before.to_rfc3339_opts(SecondsFormat::Micros, true)));
+        }
+        self.get(&format!("/channels/{channel}/threads/archived/public"), &query)
+            .await
+    }
+    /// Downloads a file from the discord CDN
+    pub async fn download(&self, url: &str) -> Result<Vec<u8>, Error> {
+        Ok(self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context(ReqwestSnafu)?
+            .bytes()
+            .await
+            .context(ReqwestSnafu)?
+            .to_vec())
+    }
+
+    /// Sends a GET request to the API `path`, waiting for its bucket if it is exhausted
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T, Error> {
+        let (route, major) = route(&Method::GET, path);
+        let mut limited = 0;
+        loop {
+            self.wait(&route, &major).await;
+            let response = self
+                .http
+                .get(format!("{API}{path}"))
+                .query(query)
+                .header("Authorization", &self.auth)
+                .send()
+                .await
+                .context(ReqwestSnafu)?;
+            self.update(&route, &major, response.headers());
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                return response
+                    .error_for_status()
+                    .context(ReqwestSnafu)?
+                    .json::<T>()
+                    .await
+                    .context(ReqwestSnafu);
+            }
+            limited += 1;
+            if limited > MAX_RATE_LIMITED {
+                whatever!("discord kept rate limiting {}", path);
+            }
+            self.rate_limited(&route, &major, response).await;
+        }
+    }
+
+    /// Sleeps until neither the global limit nor the bucket of `route` block requests
+    async fn wait(&self, route: &str, major: &str) {
+        let global = *self.global.lock().unwrap();
+        let bucket = self
+            .routes
+            .lock()
+            .unwrap()
+            .get(route)
+            .and_then(|hash| self.buckets.lock().unwrap().get(&format!("{hash}:{major}")).copied());
+        let now = Instant::now();
+        let until = [
+            global,
+            bucket.filter(|b| b.remaining == 0).map(|b| b.reset),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|until| *until > now)
+        .max();
+        if let Some(until) = until {
+            sleep_until(until).await;
+        }
+    }
+
+    /// Records the bucket state announced in the headers of a response to `route`
+    fn update(&self, route: &str, major: &str, headers: &HeaderMap) {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let Some(hash) = header("x-ratelimit-bucket") else {
+            return;
+        };
+        let remaining = header("x-ratelimit-remaining").and_then(|v| v.parse().ok());
+        let reset_after = header("x-ratelimit-reset-after").and_then(|v| v.parse::<f64>().ok());
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(route.to_string(), hash.to_string());
+        if let (Some(remaining), Some(reset_after)) = (remaining, reset_after) {
+            self.buckets.lock().unwrap().insert(
+                format!("{hash}:{major}"),
+                Bucket {
+                    remaining,
+                    reset: Instant::now() + Duration::from_secs_f64(reset_after),
+                },
+            );
+        }
+    }
+
+    /// Blocks the bucket of `route`, or every request for a global limit, for the
+    /// `retry_after` of a 429 response
+    async fn rate_limited(&self, route: &str, major: &str, response: Response) {
+        #[derive(Deserialize)]
+        struct RateLimit {
+            retry_after: f64,
+            #[serde(default)]
+            global: bool,
+        }
+        let limit = response.json::<RateLimit>().await.unwrap_or(RateLimit {
+            retry_after: 1.0,
+            global: false,
+        });
+        let reset = Instant::now() + Duration::from_secs_f64(limit.retry_after.max(0.0));
+        eprintln!(
+            "Rate limited by discord, retrying in {:.1}s",
+            limit.retry_after
+        );
+        if limit.global {
+            *self.global.lock().unwrap() = Some(reset);
+            return;
+        }
+        // a route that never announced its bucket is its own bucket
+        let hash = self
+            .routes
+            .lock()
+            .unwrap()
+            .entry(route.to_string())
+            .or_insert_with(|| route.to_string())
+            .clone();
+        self.buckets.lock().unwrap().insert(
+            format!("{hash}:{major}"),
+            Bucket {
+                remaining: 0,
+                reset,
+            },
+        );
+    }
+}
+
+/// Route of a request and its major parameter. Discord limits routes separately per
+/// channel, guild and webhook, other IDs in the path share the limit.
+fn route(method: &Method, path: &str) -> (String, String) {
+    let segments = path.split('/').collect::<Vec<_>>();
+    let mut major = String::new();
+    let route = segments
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| {
+            if segment.is_empty() || !segment.bytes().all(|b| b.is_ascii_digit()) {
+                return *segment;
+            }
+            let parent = i.checked_sub(1).map(|i| segments[i]);
+            if major.is_empty() && matches!(parent, Some("channels" | "guilds" | "webhooks")) {
+                major = segment.to_string();
+                return *segment;
+            }
+            ":id"
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    (format!("{method} {route}"), major)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes() {
+        assert_eq!(
+            route(&Method::GET, "/channels/12/messages/34"),
+            ("GET /channels/12/messages/:id".to_string(), "12".to_string())
+        );
+        assert_eq!(
+            route(&Method::GET, "/guilds/5/threads/active"),
+            ("GET /guilds/5/threads/active".to_string(), "5".to_string())
+        );
+        assert_eq!(
+            route(&Method::GET, "/users/7"),
+            ("GET /users/:id".to_string(), String::new())
+        );
+    }
+    #[test]
+    fn parse_links() {
+        let link = |s: &str| s.parse::<DiscordLink>();
+        assert_eq!(
+            link("https://ptb.discord.com/channels/1/2/3?foo=bar").unwrap(),
+            DiscordLink::Message {
+                guild: Some(1),
+                channel: 2,
+                message: 3
+            }
+        );
+        assert_eq!(
+            link("https://canary.discordapp.com/channels/1/2/").unwrap(),
+            DiscordLink::Channel {
+                guild: Some(1),
+                channel: 2
+            }
+        );
+        assert_eq!(
+            link("https://discord.com/channels/1/2/threads/4").unwrap(),
+            DiscordLink::Thread {
+                guild: Some(1),
+                parent: 2,
+                thread: 4
+            }
+        );
+        assert_eq!(
+            link("https://discord.com/channels/@me/2/3").unwrap().guild(),
+            None
+        );
+        assert_eq!(link("https://discord.com/channels/1").unwrap(), DiscordLink::Guild(1));
+        assert!(link("https://example.com/channels/1/2/3").is_err());
+        assert!(link("https://discord.com/invite/abc").is_err());
+        assert!(link("https://discord.com/channels/1/two").is_err());
+
+        let guild = GuildInfo {
+            id: 1,
+            name: "Test".to_string(),
+            eo_src: 2,
+            leg_src: 5,
+            case_src: 6,
+            categories: Default::default(),
+            index: Default::default(),
+            mentions: Default::default(),
+        };
+        let id = |s: &str, target| document_id(s, &guild, target);
+        assert_eq!(id(" 42 ", Target::Legislation).unwrap(), 42);
+        assert_eq!(id("https://discord.com/channels/1/7", Target::EO).unwrap(), 7);
+        assert_eq!(id("https://discord.com/channels/1/7/9", Target::EO).unwrap(), 7);
+        assert_eq!(id("https://discord.com/channels/1/2/threads/7", Target::EO).unwrap(), 7);
+        assert_eq!(id("https://discord.com/channels/1/5/9", Target::Legislation).unwrap(), 9);
+        assert!(id("https://discord.com/channels/1/6/9", Target::Legislation).is_err());
+        assert!(id("https://discord.com/channels/3/5/9", Target::Legislation).is_err());
+        assert!(id("https://discord.com/channels/1/5", Target::Legislation).is_err());
+    }
+    #[test]
+    fn parse_message() {
+        let message = serde_json::from_value::<Message>(serde_json::json!({
+            "id": "1348749282349416529", "channel_id": "1348749282349416529", "type": 0,
+            "author": {"id": "3", "username": "f3rri5_", "global_name": "Ferris",
+                "discriminator": "0"},
+            "content": "Lorum Ipsum", "timestamp": "2025-03-10T20:08:07.462000+00:00",
+            "edited_timestamp": null,
+            "attachments": [{"id": "7", "filename": "seal.png", "size": 10,
+                "url": "https://cdn.discordapp.com/attachments/2/7/seal.png"}]
+        }))
+        .unwrap();
+        assert_eq!(message.id, 1348749282349416529);
+        assert_eq!(message.author.username, "f3rri5_");
+        assert_eq!(message.author.global_name.as_deref(), Some("Ferris"));
+        assert_eq!(message.attachments[0].id, 7);
+        assert_eq!(message.attachments[0].content_type, None);
+        let thread = serde_json::from_value::<Channel>(serde_json::json!({
+            "id": "10", "type": 11, "guild_id": "1", "parent_id": "2", "owner_id": "3",
+            "name": "EO 12 | Archives",
+            "thread_metadata": {"archived": true, "archive_timestamp": "2025-03-11T08:00:00+00:00",
+                "auto_archive_duration": 1440, "locked": false}
+        }))
+        .unwrap();
+        assert_eq!(thread.parent_id, Some(2));
+        assert!(thread.thread_metadata.unwrap().archived);
+    }
+}
@@ -38,4 +38,24 @@ pub enum Error {
     TokioError {
         source: tokio::task::JoinError,
     },
+    #[snafu(display("{} is protected: {}", title, info))]
+    ProtectedPage { title: String, info: String },
+    #[snafu(display("Edit to {} was blocked by an abuse filter: {}", title, info))]
+    AbuseFilter { title: String, info: String },
+    #[snafu(display("Edit to {} requires solving a captcha", title))]
+    Captcha { title: String },
+    #[snafu(display("Invalid or expired CSRF token"))]
+    BadToken,
+    #[snafu(display("Not logged in as {}", user))]
+    AssertUserFailed { user: String },
+    #[snafu(display("{} was changed by someone else since it was fetched", title))]
+    EditConflict { title: String },
+    #[snafu(display("{} already exists", title))]
+    PageExists { title: String },
+    #[snafu(display("{} does not exist", title))]
+    PageMissing { title: String },
+    #[snafu(display("Invalid discord link {}: {}", link, reason))]
+    InvalidLink { link: String, reason: String },
+    #[snafu(display("MediaWiki error {}: {}", code, info))]
+    MediaWikiError { code: String, info: String },
 }
\ No newline at end of file
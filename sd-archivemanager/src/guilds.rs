@@ -3,7 +3,10 @@ use std::{fs::OpenOptions, io::Write};
 use serde::{Deserialize, Serialize};
 use snafu::{OptionExt, ResultExt};
 
-use crate::error::{Error, FileNotFoundSnafu, IoSnafu};
+use crate::{
+    error::{Error, FileNotFoundSnafu, IoSnafu},
+    regex::PerTarget,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Guilds {
@@ -16,6 +19,60 @@ pub struct GuildInfo {
     pub name: String,
     pub eo_src: u64,
     pub leg_src: u64,
+    /// Channel court verdicts are posted in. `0` if the guild has no court archive.
+    #[serde(default)]
+    pub case_src: u64,
+    /// Default categories per target, added to every page archived from this guild.
+    /// Names may contain `{year}`, `{author}` and `{title}`, which are filled in from the
+    /// archived document, e.g. `Executive Orders of {year}`.
+    #[serde(default, skip_serializing_if = "PerTarget::is_empty")]
+    pub categories: PerTarget<Vec<String>>,
+    /// Title of the wiki page listing every archived document per target,
+    /// targets without one get no index
+    #[serde(default, skip_serializing_if = "PerTarget::is_empty")]
+    pub index: PerTarget<Option<String>>,
+    /// How mentions, custom emoji and timestamps in scraped messages are rendered
+    #[serde(default, skip_serializing_if = "MentionSettings::is_default")]
+    pub mentions: MentionSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct MentionSettings {
+    /// Replace user, role and channel mentions with the names looked up on discord
+    pub resolve: bool,
+    pub emoji: EmojiStyle,
+    /// Render `<t:...>` timestamps as UTC dates in the style they were posted with
+    pub timestamps: bool,
+}
+
+impl MentionSettings {
+    pub fn is_default(&self) -> bool {
+        *self == MentionSettings::default()
+    }
+}
+
+impl Default for MentionSettings {
+    fn default() -> Self {
+        MentionSettings {
+            resolve: true,
+            emoji: EmojiStyle::default(),
+            timestamps: true,
+        }
+    }
+}
+
+/// How custom emoji like `<:name:id>` are rendered
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EmojiStyle {
+    /// `:name:`
+    #[default]
+    Text,
+    /// The emoji's CDN URL, shown as an image if the wiki allows external images
+    Image,
+    /// Leave the markup as it is
+    Keep,
 }
 
 impl Guilds {
@@ -83,12 +140,20 @@ impl Default for Guilds {
                     name: "SimDemocracy".to_string(),
                     eo_src: 1232486399295619103,
                     leg_src: 1346304876581228554,
+                    case_src: 0,
+                    categories: PerTarget::default(),
+                    index: PerTarget::default(),
+                    mentions: MentionSettings::default(),
                 },
                 GuildInfo {
                     id: 1342703437371932712,
                     name: "National Archives".to_string(),
                     eo_src: 1348711268877467738,
                     leg_src: 1348710825619230750,
+                    case_src: 0,
+                    categories: PerTarget::default(),
+                    index: PerTarget::default(),
+                    mentions: MentionSettings::default(),
                 },
             ],
         };
@@ -126,12 +191,14 @@ id = 554769523635650580
 name = "SimDemocracy"
 eo_src = 1232486399295619103
 leg_src = 1346304876581228554
+case_src = 0
 
 [[guilds]]
 id = 1342703437371932712
 name = "National Archives"
 eo_src = 1348711268877467738
 leg_src = 1348710825619230750
+case_src = 0
 "#,
             content
         );
@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::ResultExt;
+use tokio::fs;
+use xdg::BaseDirectories;
+
+use crate::{
+    error::{Error, IoSnafu, JSONSnafu, XdgSnafu},
+    regex::Target,
+    store::write_atomic,
+};
+
+/// A document that made it to the wiki
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LedgerEntry {
+    pub guild: u64,
+    pub target: Target,
+    /// Discord message (or thread) ID the document was scraped from
+    pub id: u64,
+    pub title: String,
+    /// Revision holding the uploaded content, if the wiki reported one
+    pub revid: Option<u64>,
+    /// [content_hash] of the uploaded wikitext
+    pub hash: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// Record of every upload, kept in `$XDG_DATA_HOME/sd-archivemanager/ledger.json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Ledger {
+    /// Loads the ledger, starting an empty one if nothing was uploaded yet
+    pub async fn load() -> Result<Self, Error> {
+        let xdg = BaseDirectories::with_prefix("sd-archivemanager").context(XdgSnafu)?;
+        Self::load_from(xdg.get_data_home().join("ledger.json")).await
+    }
+    pub async fn load_from(path: PathBuf) -> Result<Self, Error> {
+        let mut ledger = if path.is_file() {
+            let content = fs::read(&path).await.context(IoSnafu { file: &path })?;
+            serde_json::from_slice::<Ledger>(&content).context(JSONSnafu)?
+        } else {
+            Ledger::default()
+        };
+        ledger.path = path;
+        Ok(ledger)
+    }
+    /// Writes the ledger with [write_atomic]
+    pub async fn save(&self) -> Result<(), Error> {
+        let content = serde_json::to_vec_pretty(self).context(JSONSnafu)?;
+        write_atomic(&self.path, &content).await
+    }
+    pub fn get(&self, guild: u64, target: Target, id: u64) -> Option<&LedgerEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.guild == guild && e.target == target && e.id == id)
+    }
+    pub fn entries(&self) -> &Vec<LedgerEntry> {
+        &self.entries
+    }
+    /// Adds an entry, replacing the previous entry of the same message. NOTE: It does not save to file.
+    pub fn record(&mut self, entry: LedgerEntry) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.guild == entry.guild && e.target == entry.target && e.id == entry.id)
+        {
+            Some(old) => *old = entry,
+            None => self.entries.push(entry),
+        }
+    }
+}
+
+/// Hex encoded SHA-256 of rendered wikitext
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash() {
+        assert_eq!(
+            content_hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+    #[tokio::test]
+    async fn record_and_reload() {
+        let path = std::env::temp_dir().join("sd-archivemanager-ledger-test.json");
+        let _ = fs::remove_file(&path).await;
+        let mut ledger = Ledger::load_from(path.clone()).await.unwrap();
+        let entry = LedgerEntry {
+            guild: 1,
+            target: Target::Legislation,
+            id: 42,
+            title: "Some Act".to_string(),
+            revid: Some(10),
+            hash: content_hash("a"),
+            uploaded_at: Utc::now(),
+        };
+        ledger.record(entry.clone());
+        ledger.record(LedgerEntry {
+            revid: Some(11),
+            ..entry.clone()
+        });
+        ledger.save().await.unwrap();
+        let ledger = Ledger::load_from(path.clone()).await.unwrap();
+        assert_eq!(ledger.entries().len(), 1);
+        assert_eq!(ledger.get(1, Target::Legislation, 42).unwrap().revid, Some(11));
+        assert_eq!(ledger.get(1, Target::EO, 42), None);
+        fs::remove_file(&path).await.unwrap();
+    }
+}
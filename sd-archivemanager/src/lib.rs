@@ -1,23 +1,22 @@
-use tokio::sync::{Mutex, MutexGuard};
+use tokio::sync::Mutex;
 
 use config::Config;
 pub use once_cell::sync::Lazy;
 
 pub mod config;
 pub mod converters;
+pub mod diff;
+pub mod discord;
 pub mod error;
 pub mod guilds;
+pub mod ledger;
+pub mod mediawiki;
+pub mod mentions;
 pub mod regex;
+pub mod retry;
 pub mod plugins;
+pub mod session;
+pub mod store;
 
 pub static CONFIG: Lazy<Mutex<Config>> =
     Lazy::new(|| Mutex::new(Config::load().unwrap_or_default()));
-
-#[inline(always)]
-fn format_auth(cfg: &MutexGuard<'_, Config>) -> String {
-    if cfg.bot {
-        format!("Bot {}", cfg.token)
-    } else {
-        cfg.token.clone()
-    }
-}
@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::{
+    Client,
+    multipart::{Form, Part},
+};
+use serde_json::Value;
+use snafu::{OptionExt, ResultExt, ensure_whatever};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+
+use crate::{
+    Lazy,
+    config::Config,
+    converters::EditParams,
+    error::{Error, ReqwestSnafu},
+    retry,
+    session::Session,
+};
+
+/// Wiki client shared by every upload of the process, opened on first use
+pub static WIKI: Lazy<Mutex<Option<MediaWikiClient>>> = Lazy::new(|| Mutex::new(None));
+
+/// Locks [WIKI], opening the client first if this is the first use
+pub async fn shared(cfg: &Config) -> Result<MappedMutexGuard<'static, MediaWikiClient>, Error> {
+    let mut wiki = WIKI.lock().await;
+    if wiki.is_none() {
+        *wiki = Some(MediaWikiClient::open(cfg).await?);
+    }
+    Ok(MutexGuard::map(wiki, |wiki| wiki.as_mut().unwrap()))
+}
+
+/// Result of a successful `action=edit`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditResult {
+    pub title: String,
+    pub pageid: u64,
+    /// Revision created by the edit, [None] if the content did not change
+    pub newrevid: Option<u64>,
+    /// Revision the edit was based on, [None] if the page was created
+    pub oldrevid: Option<u64>,
+    pub nochange: bool,
+}
+
+/// Latest revision of a wiki page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    pub revid: u64,
+    pub timestamp: DateTime<Utc>,
+    pub content: String,
+}
+
+/// A category as reported by `prop=categoryinfo`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryInfo {
+    /// Name without the `Category:` prefix, normalized by the wiki
+    pub name: String,
+    /// Whether the category page exists, categories without a page can still have members
+    pub exists: bool,
+    /// Pages, files and subcategories in the category
+    pub members: u64,
+}
+
+/// Client for the MediaWiki API at [Config::endpoint].
+/// Requests go through the persisted [Session] and are retried according to [Config::retry],
+/// writes log in on first use and share one cached CSRF token.
+#[derive(Debug)]
+pub struct MediaWikiClient {
+    cfg: Config,
+    session: Session,
+}
+
+impl MediaWikiClient {
+    /// Opens the session stored for `cfg`, nothing is sent to the wiki yet
+    pub async fn open(cfg: &Config) -> Result<Self, Error> {
+        Ok(Self::with_session(cfg.clone(), Session::open(cfg).await?))
+    }
+    pub fn with_session(cfg: Config, session: Session) -> Self {
+        MediaWikiClient { cfg, session }
+    }
+    pub fn endpoint(&self) -> &str {
+        &self.cfg.endpoint
+    }
+    /// HTTP client carrying the session cookies
+    pub fn http(&self) -> &Client {
+        self.session.client()
+    }
+    /// Logs in again, regardless of whether the stored session is still valid
+    pub async fn login(&mut self) -> Result<(), Error> {
+        self.session.login(&self.cfg).await
+    }
+    /// CSRF token for writes, logging in first if needed
+    pub async fn csrf_token(&mut self) -> Result<String, Error> {
+        self.session.csrf_token(&self.cfg).await
+    }
+    /// Persists the session cookies
+    pub async fn save(&self) -> Result<(), Error> {
+        self.session.save().await
+    }
+
+    /// Fetches the latest revision of `title`, [None] if the page does not exist
+    pub async fn page(&self, title: &str) -> Result<Option<Revision>, Error> {
+        let mut form = HashMap::new();
+        form.insert("action", "query");
+        form.insert("prop", "revisions");
+        form.insert("rvprop", "content|ids|timestamp");
+        form.insert("rvslots", "main");
+        form.insert("titles", title);
+        form.insert("format", "json");
+        form.insert("formatversion", "2");
+        let response = self.get(&form).await?;
+        if let Some(err) = api_error(&response, title, &self.cfg) {
+            return Err(err);
+        }
+        let page = &response["query"]["pages"][0];
+        ensure_whatever!(page.is_object(), "invalid response: {}", &response);
+        if page["missing"].as_bool() == Some(true) {
+            return Ok(None);
+        }
+        let revision = &page["revisions"][0];
+        Ok(Some(Revision {
+            revid: revision["revid"]
+                .as_u64()
+                .whatever_context(format!("invalid response: {}", &response))?,
+            timestamp: revision["timestamp"]
+                .as_str()
+                .and_then(|t| t.parse().ok())
+                .whatever_context(format!("invalid response: {}", &response))?,
+            content: revision["slots"]["main"]["content"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        }))
+    }
+
+    /// Edits `title` to hold `content`. A stale token or login is renewed once.
+    pub async fn edit(
+        &mut self,
+        title: &str,
+        content: &str,
+        params: &EditParams,
+    ) -> Result<EditResult, Error> {
+        match self.try_edit(title, content, params).await {
+            Err(Error::BadToken | Error::AssertUserFailed { .. }) => {
+                self.session.expire();
+                self.try_edit(title, content, params).await
+            }
+            edit => edit,
+        }
+    }
+    async fn try_edit(
+        &mut self,
+        title: &str,
+        content: &str,
+        params: &EditParams,
+    ) -> Result<EditResult, Error> {
+        let token = self.csrf_token().await?;
+        let tags = params.tags.join("|");
+        let basetimestamp = params
+            .basetimestamp
+            .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true));
+        let starttimestamp = params
+            .starttimestamp
+            .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true));
+        let mut form = self.write_form("edit", &token);
+        form.insert("title", title);
+        form.insert("text", content);
+        form.insert("summary", &params.summary);
+        // the API treats boolean parameters as true whenever they are present
+        if params.bot {
+            form.insert("bot", "1");
+        }
+        if params.minor {
+            form.insert("minor", "1");
+        }
+        if !tags.is_empty() {
+            form.insert("tags", &tags);
+        }
+        if let Some(basetimestamp) = &basetimestamp {
+            form.insert("basetimestamp", basetimestamp);
+        }
+        if let Some(starttimestamp) = &starttimestamp {
+            form.insert("starttimestamp", starttimestamp);
+        }
+        if params.createonly {
+            form.insert("createonly", "1");
+        }
+        if params.nocreate {
+            form.insert("nocreate", "1");
+        }
+        let response = self.post(&form).await?;
+        parse_edit(&response, title, &self.cfg)
+    }
+
+    /// Moves `from` to `to` with `action=move`, leaving a redirect behind.
+    /// Returns `false` if `from` does not exist (anymore), so there was nothing to move.
+    pub async fn move_page(&mut self, from: &str, to: &str, reason: &str) -> Result<bool, Error> {
+        let token = self.csrf_token().await?;
+        let mut form = self.write_form("move", &token);
+        form.insert("from", from);
+        form.insert("to", to);
+        form.insert("reason", reason);
+        form.insert("movetalk", "1");
+        let response = self.post(&form).await?;
+        // the title in errors is the destination, `missingtitle` can only be about the source
+        match api_error(&response, to, &self.cfg) {
+            Some(Error::PageMissing { .. }) => Ok(false),
+            Some(err) => Err(err),
+            None => Ok(true),
+        }
+    }
+
+    /// Uploads `file` as `File:<filename>` with `text` as its description page.
+    /// Warnings, e.g. about duplicates of other files, are ignored.
+    pub async fn upload_file(
+        &mut self,
+        filename: &str,
+        file: Vec<u8>,
+        content_type: Option<&str>,
+        comment: &str,
+        text: &str,
+    ) -> Result<(), Error> {
+        let token = self.csrf_token().await?;
+        let title = format!("File:{filename}");
+        // multipart bodies can not be cloned, so the form is built again for every attempt
+        let form = || {
+            let mut part = Part::bytes(file.clone()).file_name(filename.to_string());
+            if let Some(content_type) = content_type {
+                part = part.mime_str(content_type).context(ReqwestSnafu)?;
+            }
+            Ok::<_, Error>(
+                Form::new()
+                    .text("action", "upload")
+                    .text("format", "json")
+                    .text("filename", filename.to_string())
+                    .text("comment", comment.to_string())
+                    .text("text", text.to_string())
+                    .text("ignorewarnings", "1")
+                    .text("token", token.clone())
+                    .part("file", part),
+            )
+        };
+        // fail on an invalid content type before anything is sent
+        form()?;
+        let response = retry::send(
+            || {
+                self.http()
+                    .post(&self.cfg.endpoint)
+                    .multipart(form().expect("checked above"))
+            },
+            &self.cfg.retry,
+        )
+        .await?;
+        if let Some(err) = api_error(&response, &title, &self.cfg) {
+            return Err(err);
+        }
+        ensure_whatever!(
+            response["upload"]["result"].as_str() == Some("Success"),
+            "failed to upload {}: {}",
+            title,
+            response
+        );
+        Ok(())
+    }
+
+    /// Looks up the categories `names` (without the `Category:` prefix)
+    pub async fn categories(&self, names: &[String]) -> Result<Vec<CategoryInfo>, Error> {
+        let mut categories = Vec::new();
+        // the API accepts at most 50 titles per query
+        for chunk in names.chunks(50) {
+            let titles = chunk
+                .iter()
+                .map(|name| format!("Category:{name}"))
+                .collect::<Vec<_>>()
+                .join("|");
+            let mut form = HashMap::new();
+            form.insert("action", "query");
+            form.insert("prop", "categoryinfo");
+            form.insert("titles", &titles);
+            form.insert("format", "json");
+            form.insert("formatversion", "2");
+            let response = self.get(&form).await?;
+            if let Some(err) = api_error(&response, &titles, &self.cfg) {
+                return Err(err);
+            }
+            categories.extend(parse_categories(&response)?);
+        }
+        Ok(categories)
+    }
+
+    /// Parameters every write shares, asserting the session still belongs to the configured user
+    fn write_form<'a>(&'a self, action: &'a str, token: &'a str) -> HashMap<&'a str, &'a str> {
+        let mut form = HashMap::new();
+        form.insert("action", action);
+        form.insert("token", token);
+        form.insert("format", "json");
+        match self.cfg.wiki_user() {
+            Some(user) => form.insert("assertuser", user),
+            None => form.insert("assert", "user"),
+        };
+        form
+    }
+    async fn get(&self, query: &HashMap<&str, &str>) -> Result<Value, Error> {
+        retry::send(
+            || self.http().get(&self.cfg.endpoint).query(query),
+            &self.cfg.retry,
+        )
+        .await
+    }
+    async fn post(&self, form: &HashMap<&str, &str>) -> Result<Value, Error> {
+        retry::send(
+            || self.http().post(&self.cfg.endpoint).form(form),
+            &self.cfg.retry,
+        )
+        .await
+    }
+}
+
+/// Maps the `error` object of an API response to an [Error]
+pub(crate) fn api_error(response: &Value, title: &str, cfg: &Config) -> Option<Error> {
+    let error = response.get("error")?;
+    let code = error["code"].as_str().unwrap_or_default();
+    let info = error["info"].as_str().unwrap_or_default().to_string();
+    Some(mw_error(code, info, title, cfg))
+}
+
+fn mw_error(code: &str, info: String, title: &str, cfg: &Config) -> Error {
+    let title = title.to_string();
+    match code {
+        "protectedpage" | "cascadeprotected" | "protectedtitle" | "protectednamespace"
+        | "protectednamespace-interface" => Error::ProtectedPage { title, info },
+        c if c.starts_with("abusefilter") => Error::AbuseFilter { title, info },
+        "captcha" => Error::Captcha { title },
+        "badtoken" | "notoken" => Error::BadToken,
+        "editconflict" | "pagedeleted" => Error::EditConflict { title },
+        "articleexists" => Error::PageExists { title },
+        "missingtitle" => Error::PageMissing { title },
+        "assertuserfailed" | "assertnameduserfailed" | "assertbotfailed" => {
+            Error::AssertUserFailed {
+                user: cfg.wiki_user().unwrap_or(&cfg.login).to_string(),
+            }
+        }
+        _ => Error::MediaWikiError {
+            code: code.to_string(),
+            info,
+        },
+    }
+}
+
+/// Parses the response to `action=edit`
+pub(crate) fn parse_edit(response: &Value, title: &str, cfg: &Config) -> Result<EditResult, Error> {
+    if let Some(err) = api_error(response, title, cfg) {
+        return Err(err);
+    }
+    let edit = &response["edit"];
+    if edit["result"].as_str() != Some("Success") {
+        // hooks like ConfirmEdit and older AbuseFilter versions report failures inside `edit`
+        if edit.get("captcha").is_some() {
+            return Err(Error::Captcha {
+                title: title.to_string(),
+            });
+        }
+        let code = edit["code"].as_str().unwrap_or("unknown");
+        let info = edit["info"]
+            .as_str()
+            .or(edit["warning"].as_str())
+            .map_or_else(|| response.to_string(), str::to_string);
+        return Err(mw_error(code, info, title, cfg));
+    }
+    Ok(EditResult {
+        title: edit["title"].as_str().unwrap_or(title).to_string(),
+        pageid: edit["pageid"]
+            .as_u64()
+            .whatever_context(format!("invalid response: {}", response))?,
+        newrevid: edit["newrevid"].as_u64(),
+        oldrevid: edit["oldrevid"].as_u64().filter(|id| *id != 0),
+        nochange: edit.get("nochange").is_some(),
+    })
+}
+
+/// Parses the pages of a `prop=categoryinfo` response
+fn parse_categories(response: &Value) -> Result<Vec<CategoryInfo>, Error> {
+    let pages = response["query"]["pages"]
+        .as_array()
+        .whatever_context(format!("invalid response: {}", response))?;
+    pages
+        .iter()
+        .map(|page| {
+            let title = page["title"]
+                .as_str()
+                .whatever_context(format!("invalid response: {}", response))?;
+            Ok(CategoryInfo {
+                name: title.split_once(':').map_or(title, |(_, name)| name).to_string(),
+                exists: page["missing"].as_bool() != Some(true),
+                members: page["categoryinfo"]["size"].as_u64().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use xdg::BaseDirectories;
+
+    use super::*;
+    use crate::CONFIG;
+
+    #[test]
+    fn parse_edit_response() {
+        let cfg = Config {
+            login: "Archivist".to_string(),
+            ..Config::for_tests()
+        };
+        let ok = serde_json::json!({"edit": {"result": "Success", "pageid": 12, "title": "EO 1",
+            "contentmodel": "wikitext", "oldrevid": 40, "newrevid": 41}});
+        assert_eq!(
+            parse_edit(&ok, "EO 1", &cfg).unwrap(),
+            EditResult {
+                title: "EO 1".to_string(),
+                pageid: 12,
+                newrevid: Some(41),
+                oldrevid: Some(40),
+                nochange: false,
+            }
+        );
+        let protected = serde_json::json!({"error": {"code": "protectedpage",
+            "info": "This page has been protected to prevent editing or other actions."}});
+        assert!(matches!(
+            parse_edit(&protected, "EO 1", &cfg),
+            Err(Error::ProtectedPage { .. })
+        ));
+        let assert = serde_json::json!({"error": {"code": "assertnameduserfailed", "info": ""}});
+        assert!(matches!(
+            parse_edit(&assert, "EO 1", &cfg),
+            Err(Error::AssertUserFailed { user }) if user == "Archivist"
+        ));
+        let captcha = serde_json::json!({"edit": {"result": "Failure",
+            "captcha": {"type": "image", "id": "1"}}});
+        assert!(matches!(
+            parse_edit(&captcha, "EO 1", &cfg),
+            Err(Error::Captcha { .. })
+        ));
+        let filter = serde_json::json!({"edit": {"result": "Failure",
+            "code": "abusefilter-disallowed", "info": "Hit AbuseFilter: Blanking"}});
+        assert!(matches!(
+            parse_edit(&filter, "EO 1", &cfg),
+            Err(Error::AbuseFilter { .. })
+        ));
+        let conflict = serde_json::json!({"error": {"code": "editconflict", "info": "Edit conflict."}});
+        assert!(matches!(
+            parse_edit(&conflict, "EO 1", &cfg),
+            Err(Error::EditConflict { title }) if title == "EO 1"
+        ));
+        let unknown = serde_json::json!({"error": {"code": "readonly", "info": "The wiki is read-only"}});
+        assert!(matches!(
+            parse_edit(&unknown, "EO 1", &cfg),
+            Err(Error::MediaWikiError { code, .. }) if code == "readonly"
+        ));
+    }
+    #[test]
+    fn category_info() {
+        let response = serde_json::json!({"query": {"pages": [
+            {"ns": 14, "title": "Category:Laws", "missing": true,
+                "categoryinfo": {"size": 3, "pages": 3, "files": 0, "subcats": 0}},
+            {"pageid": 5, "ns": 14, "title": "Category:Laws of 2025",
+                "categoryinfo": {"size": 1, "pages": 1, "files": 0, "subcats": 0}},
+            {"ns": 14, "title": "Category:Empty", "missing": true}
+        ]}});
+        assert_eq!(
+            parse_categories(&response).unwrap(),
+            vec![
+                CategoryInfo {
+                    name: "Laws".to_string(),
+                    exists: false,
+                    members: 3
+                },
+                CategoryInfo {
+                    name: "Laws of 2025".to_string(),
+                    exists: true,
+                    members: 1
+                },
+                CategoryInfo {
+                    name: "Empty".to_string(),
+                    exists: false,
+                    members: 0
+                },
+            ]
+        );
+    }
+    #[tokio::test]
+    async fn test_get_login() {
+        let xdg = BaseDirectories::with_prefix("sd-archivemanager").unwrap();
+        let mut wiki = MediaWikiClient::open(&*CONFIG.lock().await).await.unwrap();
+        wiki.login().await.unwrap();
+        wiki.csrf_token().await.unwrap();
+        fs::remove_file(xdg.place_data_file("cookies.json").unwrap()).unwrap();
+    }
+}
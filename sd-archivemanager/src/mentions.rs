@@ -0,0 +1,203 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    sync::Mutex,
+};
+
+use chrono::DateTime;
+use regex::{Captures, Regex};
+
+use crate::{
+    Lazy,
+    discord::DiscordClient,
+    error::Error,
+    guilds::{EmojiStyle, MentionSettings},
+};
+
+static MENTION: Lazy<Regex> = Lazy::new(|| Regex::new(r"<(@!?|@&|#)(\d+)>").unwrap());
+static EMOJI: Lazy<Regex> = Lazy::new(|| Regex::new(r"<(a?):(\w+):(\d+)>").unwrap());
+static TIMESTAMP: Lazy<Regex> = Lazy::new(|| Regex::new(r"<t:(-?\d+)(?::([tTdDfFR]))?>").unwrap());
+
+/// Looked up names by guild and mention, [None] if the lookup failed
+type Names = HashMap<(Option<u64>, Mention), Option<String>>;
+
+/// Names looked up during this run
+static NAMES: Lazy<Mutex<Names>> = Lazy::new(Mutex::default);
+
+/// A user, role or channel mention like `<@123>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mention {
+    User(u64),
+    Role(u64),
+    Channel(u64),
+}
+
+impl Mention {
+    fn parse(caps: &Captures) -> Option<Self> {
+        let id = caps[2].parse().ok()?;
+        Some(match &caps[1] {
+            "@" | "@!" => Mention::User(id),
+            "@&" => Mention::Role(id),
+            _ => Mention::Channel(id),
+        })
+    }
+    fn render(&self, name: Option<&str>) -> String {
+        // the placeholders discord shows for mentions it can not resolve either
+        match (self, name) {
+            (Mention::User(_), None) => "@unknown-user".to_string(),
+            (Mention::Role(_), None) => "@deleted-role".to_string(),
+            (Mention::Channel(_), None) => "#unknown".to_string(),
+            (Mention::User(_) | Mention::Role(_), Some(name)) => format!("@{name}"),
+            (Mention::Channel(_), Some(name)) => format!("#{name}"),
+        }
+    }
+}
+
+/// Renders the mentions, custom emoji and timestamps in `content`, a message posted in `guild`,
+/// according to `settings`. Names are looked up once per run.
+pub async fn resolve(
+    content: &str,
+    guild: Option<u64>,
+    settings: &MentionSettings,
+    discord: &DiscordClient,
+) -> String {
+    let mut names = HashMap::new();
+    if settings.resolve {
+        for mention in MENTION
+            .captures_iter(content)
+            .filter_map(|caps| Mention::parse(&caps))
+        {
+            if let Entry::Vacant(entry) = names.entry(mention) {
+                entry.insert(lookup(guild, mention, discord).await);
+            }
+        }
+    }
+    render(content, &names, settings)
+}
+
+async fn lookup(guild: Option<u64>, mention: Mention, discord: &DiscordClient) -> Option<String> {
+    if let Some(name) = NAMES.lock().unwrap().get(&(guild, mention)) {
+        return name.clone();
+    }
+    let name: Result<Option<String>, Error> = match (mention, guild) {
+        (Mention::User(id), Some(guild)) => match discord.member(guild, id).await {
+            Ok(member) => Ok(member
+                .nick
+                .or(member.user.global_name)
+                .or(Some(member.user.username))),
+            // users who left the guild
+            Err(_) => discord
+                .user(id)
+                .await
+                .map(|user| user.global_name.or(Some(user.username))),
+        },
+        (Mention::User(id), None) => discord
+            .user(id)
+            .await
+            .map(|user| user.global_name.or(Some(user.username))),
+        (Mention::Channel(id), _) => discord.channel(id).await.map(|channel| channel.name),
+        // one request gets every role of the guild
+        (Mention::Role(_), Some(guild)) => discord.roles(guild).await.map(|roles| {
+            let mut names = NAMES.lock().unwrap();
+            for role in roles {
+                names.insert((Some(guild), Mention::Role(role.id)), Some(role.name));
+            }
+            names.get(&(Some(guild), mention)).cloned().flatten()
+        }),
+        (Mention::Role(_), None) => Ok(None),
+    };
+    let name = name.unwrap_or_else(|err| {
+        eprintln!("Could not resolve {mention:?}: {err}");
+        None
+    });
+    NAMES.lock().unwrap().insert((guild, mention), name.clone());
+    name
+}
+
+/// Replaces the markup in `content`, mentions missing from `names` are left as they are
+pub(crate) fn render(
+    content: &str,
+    names: &HashMap<Mention, Option<String>>,
+    settings: &MentionSettings,
+) -> String {
+    let content = MENTION.replace_all(content, |caps: &Captures| {
+        match Mention::parse(caps).and_then(|m| Some((m, names.get(&m)?))) {
+            Some((mention, name)) => mention.render(name.as_deref()),
+            None => caps[0].to_string(),
+        }
+    });
+    let content = EMOJI.replace_all(&content, |caps: &Captures| match settings.emoji {
+        EmojiStyle::Text => format!(":{}:", &caps[2]),
+        EmojiStyle::Image => {
+            let ext = if caps[1].is_empty() { "png" } else { "gif" };
+            format!("https://cdn.discordapp.com/emojis/{}.{ext}", &caps[3])
+        }
+        EmojiStyle::Keep => caps[0].to_string(),
+    });
+    if !settings.timestamps {
+        return content.into_owned();
+    }
+    TIMESTAMP
+        .replace_all(&content, |caps: &Captures| {
+            let style = caps.get(2).map_or("f", |style| style.as_str());
+            caps[1]
+                .parse()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                .map(|date| date.format(timestamp_format(style)).to_string())
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// Date format matching a discord timestamp style, relative times are shown in full
+fn timestamp_format(style: &str) -> &'static str {
+    match style {
+        "t" => "%H:%M UTC",
+        "T" => "%H:%M:%S UTC",
+        "d" => "%d/%m/%Y",
+        "D" => "%-d %B %Y",
+        "F" => "%A, %-d %B %Y %H:%M UTC",
+        _ => "%-d %B %Y %H:%M UTC",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_markup() {
+        let names = HashMap::from([
+            (Mention::User(1), Some("Alice".to_string())),
+            (Mention::Role(2), Some("Senate".to_string())),
+            (Mention::Channel(3), Some("general".to_string())),
+            (Mention::Channel(4), None),
+        ]);
+        let settings = MentionSettings::default();
+        assert_eq!(
+            render("<@1> <@!1> <@&2> <#3> <#4> <@5>", &names, &settings),
+            "@Alice @Alice @Senate #general #unknown <@5>"
+        );
+        assert_eq!(
+            render("<:yes:10> <a:wave:11>", &names, &settings),
+            ":yes: :wave:"
+        );
+        assert_eq!(
+            render(
+                "<t:1700000000:F>, <t:1700000000:d>, <t:1700000000>",
+                &names,
+                &settings
+            ),
+            "Tuesday, 14 November 2023 22:13 UTC, 14/11/2023, 14 November 2023 22:13 UTC"
+        );
+        let settings = MentionSettings {
+            resolve: false,
+            emoji: EmojiStyle::Image,
+            timestamps: false,
+        };
+        assert_eq!(
+            render("<a:wave:11> <t:1700000000:R>", &HashMap::new(), &settings),
+            "https://cdn.discordapp.com/emojis/11.gif <t:1700000000:R>"
+        );
+    }
+}
@@ -26,6 +26,11 @@ pub struct Profile {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RegexManager {
     pub profile: Option<Vec<Profile>>,
+    /// Alias rules, deriving alternate titles that redirect to the archived page.
+    /// The first match in the title (or the content if `for_title` is false) is expanded
+    /// into `replace`, e.g. `EO (\d+)` with `Executive Order $1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<Vec<Profile>>,
 }
 
 impl RegexManager {
@@ -92,7 +97,7 @@ impl RegexManager {
                 .unwrap()
             }
         });
-        if self.profile.is_some() {
+        if self.profile.is_some() || self.alias.is_some() {
             file.await.context(TokioSnafu)?.write_all(toml::to_string(self).unwrap().as_bytes()).await
                 .context(IoSnafu {
                     file: path.to_string_lossy().to_string(),
@@ -132,27 +137,39 @@ impl RegexManager {
     /// assert_eq!(vec![Profile {author = "*", ...}], regex.get_regexs())
     /// ```
     pub fn get_regexs(&self, user: &str, target: Target) -> Vec<&Profile> {
-        match &self.profile {
-            None => vec![],
-            Some(p) => {
-                let mut vec = p
-                    .iter()
-                    .filter(|x| {
-                        (x.author
-                            .split(',')
-                            .map(|y| y.trim())
-                            .collect::<Vec<_>>()
-                            .contains(&user)
-                            || x.author == "*") && target == x.target
-                    })
-                    .collect::<Vec<&Profile>>();
-                vec.sort_by(|x, y| x.name.cmp(&y.name));
-                vec
-            }
-        }
+        matching(&self.profile, user, target)
+    }
+    /// Return all alias rules valid for a given user, matched like [RegexManager::get_regexs]
+    pub fn get_aliases(&self, user: &str, target: Target) -> Vec<&Profile> {
+        matching(&self.alias, user, target)
     }
     pub fn new() -> Self {
-        RegexManager { profile: None }
+        RegexManager {
+            profile: None,
+            alias: None,
+        }
+    }
+}
+
+/// Profiles of `target` whose author list contains `user` or `*`, sorted by name
+fn matching<'a>(profiles: &'a Option<Vec<Profile>>, user: &str, target: Target) -> Vec<&'a Profile> {
+    match profiles {
+        None => vec![],
+        Some(p) => {
+            let mut vec = p
+                .iter()
+                .filter(|x| {
+                    (x.author
+                        .split(',')
+                        .map(|y| y.trim())
+                        .collect::<Vec<_>>()
+                        .contains(&user)
+                        || x.author == "*") && target == x.target
+                })
+                .collect::<Vec<&Profile>>();
+            vec.sort_by(|x, y| x.name.cmp(&y.name));
+            vec
+        }
     }
 }
 
@@ -170,7 +187,7 @@ impl Default for RegexManager {
                     .to_string(),
             })
             .unwrap();
-        let rgx = RegexManager { profile: None };
+        let rgx = RegexManager::new();
         let mut file = std::fs::OpenOptions::new()
             .write(true)
             .create(true)
@@ -190,6 +207,43 @@ pub enum Target {
     CaseLaw,
 }
 
+impl Target {
+    /// Name used in config files and on disk, same as the serde representation
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::Legislation => "legislation",
+            Target::EO => "eo",
+            Target::CaseLaw => "caselaw",
+        }
+    }
+}
+
+/// A setting with one value per [Target], written as `eo`, `legislation` and `caselaw`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
+#[serde(default)]
+pub struct PerTarget<T> {
+    pub eo: T,
+    pub legislation: T,
+    pub caselaw: T,
+}
+
+impl<T> PerTarget<T> {
+    pub fn get(&self, target: Target) -> &T {
+        match target {
+            Target::EO => &self.eo,
+            Target::Legislation => &self.legislation,
+            Target::CaseLaw => &self.caselaw,
+        }
+    }
+}
+
+impl<T: Default + PartialEq> PerTarget<T> {
+    /// Whether no target has a value set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{path::PathBuf, str::FromStr};
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::{
+    RequestBuilder, StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
+use serde_json::Value;
+use snafu::ResultExt;
+use tokio::time::sleep;
+
+use crate::{
+    config::RetrySettings,
+    error::{Error, ReqwestSnafu},
+};
+
+/// Sends the API request built by `request` and parses the JSON response, retrying with
+/// [backoff] while the wiki reports `maxlag` or `ratelimited`, answers with 429 or a 5xx
+/// status, or can not be reached. [RetrySettings::maxlag] is added to the request.
+/// Once the retries are used up the last response is returned as is, so API errors
+/// are still reported by the caller.
+pub(crate) async fn send(
+    request: impl Fn() -> RequestBuilder,
+    settings: &RetrySettings,
+) -> Result<Value, Error> {
+    let mut attempt = 0;
+    loop {
+        let mut builder = request();
+        if let Some(maxlag) = settings.maxlag {
+            builder = builder.query(&[("maxlag", maxlag)]);
+        }
+        // the outer option is whether to retry, the inner one the wait the wiki asked for
+        let (result, retry) = match builder.send().await {
+            Err(err) => {
+                let transient = err.is_connect() || err.is_timeout();
+                (Err(err).context(ReqwestSnafu), transient.then_some(None))
+            }
+            Ok(response) => {
+                let wait = retry_after(response.headers(), Utc::now());
+                let status = response.status();
+                if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+                    (
+                        response.error_for_status().map(|_| Value::Null).context(ReqwestSnafu),
+                        Some(wait),
+                    )
+                } else {
+                    let body = response.json::<Value>().await.context(ReqwestSnafu)?;
+                    let transient =
+                        matches!(body["error"]["code"].as_str(), Some("maxlag" | "ratelimited"));
+                    (Ok(body), transient.then_some(wait))
+                }
+            }
+        };
+        let Some(wait) = retry.filter(|_| attempt < settings.max_retries) else {
+            return result;
+        };
+        let delay = backoff(attempt, wait, settings);
+        eprintln!(
+            "The wiki is busy or unreachable, retrying in {}s ({}/{})",
+            delay.as_secs(),
+            attempt + 1,
+            settings.max_retries
+        );
+        sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Delay before retry number `attempt` (starting at 0): the `Retry-After` of the response
+/// if there was one, otherwise the base delay doubled per attempt up to the maximum
+pub(crate) fn backoff(attempt: u32, retry_after: Option<Duration>, settings: &RetrySettings) -> Duration {
+    retry_after.unwrap_or_else(|| {
+        let delay = settings
+            .base_delay_ms
+            .saturating_mul(2u64.saturating_pow(attempt))
+            .min(settings.max_delay_ms);
+        Duration::from_millis(delay)
+    })
+}
+
+/// Parses a `Retry-After` header, given either in seconds or as an HTTP date
+fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    // a date in the past means the request can be retried right away
+    Some((date.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn backoff_delays() {
+        let settings = RetrySettings {
+            max_retries: 5,
+            base_delay_ms: 500,
+            max_delay_ms: 3000,
+            maxlag: Some(5),
+        };
+        let delays = (0..4)
+            .map(|attempt| backoff(attempt, None, &settings).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![500, 1000, 2000, 3000]);
+        assert_eq!(
+            backoff(0, Some(Duration::from_secs(10)), &settings),
+            Duration::from_secs(10)
+        );
+        let mut headers = HeaderMap::new();
+        let now = "2025-03-10T12:00:00Z".parse().unwrap();
+        assert_eq!(retry_after(&headers, now), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("5"));
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(5)));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Mon, 10 Mar 2025 12:00:30 GMT"));
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(30)));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Mon, 10 Mar 2025 11:00:00 GMT"));
+        assert_eq!(retry_after(&headers, now), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,252 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use cookie_store::CookieStore;
+use reqwest::{
+    Client,
+    header::{AUTHORIZATION, HeaderMap, HeaderValue},
+};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::Value;
+use snafu::{OptionExt, ResultExt, ensure_whatever, whatever};
+use tokio::fs;
+use xdg::BaseDirectories;
+
+use crate::{
+    config::{Config, WikiAuth},
+    error::{CookieStoreSnafu, Error, IoSnafu, ReqwestSnafu, XdgSnafu},
+    retry,
+    store::write_atomic,
+};
+
+/// A logged in MediaWiki session. The cookie jar is persisted in
+/// `$XDG_DATA_HOME/sd-archivemanager/cookies.json` so later runs can reuse the login.
+#[derive(Debug)]
+pub struct Session {
+    client: Client,
+    cookies: Arc<CookieStoreMutex>,
+    path: PathBuf,
+    csrf: Option<String>,
+    /// Whether the login was checked (or made) since the session was opened
+    verified: bool,
+}
+
+impl Session {
+    pub async fn open(cfg: &Config) -> Result<Self, Error> {
+        let xdg = BaseDirectories::with_prefix("sd-archivemanager").context(XdgSnafu)?;
+        Self::open_at(xdg.get_data_home().join("cookies.json"), &cfg.auth).await
+    }
+    /// Opens a session with the cookie jar stored at `path`
+    pub async fn open_at(path: PathBuf, auth: &WikiAuth) -> Result<Self, Error> {
+        let store = if path.is_file() {
+            let content = fs::read(&path).await.context(IoSnafu { file: &path })?;
+            CookieStore::load_all(content.as_slice(), |s| serde_json::from_str(s))
+                .context(CookieStoreSnafu)?
+        } else {
+            CookieStore::default()
+        };
+        let cookies = Arc::new(CookieStoreMutex::new(store));
+        let mut headers = HeaderMap::new();
+        if let WikiAuth::OAuth { access_token } = auth {
+            let mut bearer = HeaderValue::from_str(&format!("Bearer {access_token}"))
+                .whatever_context("invalid OAuth access token")?;
+            bearer.set_sensitive(true);
+            headers.insert(AUTHORIZATION, bearer);
+        }
+        let client = Client::builder()
+            .cookie_provider(Arc::clone(&cookies))
+            .default_headers(headers)
+            .build()
+            .context(ReqwestSnafu)?;
+        Ok(Session {
+            client,
+            cookies,
+            path,
+            csrf: None,
+            verified: false,
+        })
+    }
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    /// Logs in again if the stored session expired, checked once per opened session
+    pub async fn ensure_login(&mut self, cfg: &Config) -> Result<(), Error> {
+        if self.verified {
+            return Ok(());
+        }
+        if !self.is_logged_in(cfg).await? {
+            self.login(cfg).await?;
+        }
+        self.verified = true;
+        Ok(())
+    }
+    /// Asks the wiki whether the session still belongs to [Config::wiki_user]
+    pub async fn is_logged_in(&self, cfg: &Config) -> Result<bool, Error> {
+        let request = || {
+            self.client.get(&cfg.endpoint).query(&[
+                ("action", "query"),
+                ("meta", "userinfo"),
+                ("format", "json"),
+                ("formatversion", "2"),
+            ])
+        };
+        let response = retry::send(request, &cfg.retry).await?;
+        Ok(logged_in_as(&response, cfg.wiki_user()))
+    }
+    /// Runs the full login flow and drops the cached CSRF token
+    pub async fn login(&mut self, cfg: &Config) -> Result<(), Error> {
+        log_in(cfg, &self.client).await?;
+        self.csrf = None;
+        self.verified = true;
+        self.save().await
+    }
+    /// CSRF token for edits, fetched once and cached until [Session::expire]
+    pub async fn csrf_token(&mut self, cfg: &Config) -> Result<String, Error> {
+        self.ensure_login(cfg).await?;
+        if let Some(token) = &self.csrf {
+            return Ok(token.clone());
+        }
+        let token = fetch_csrf(cfg, &self.client).await?;
+        self.csrf = Some(token.clone());
+        Ok(token)
+    }
+    /// Forgets the cached token and login check, for when the wiki rejected either
+    pub fn expire(&mut self) {
+        self.csrf = None;
+        self.verified = false;
+    }
+    /// Writes the cookie jar with [write_atomic]
+    pub async fn save(&self) -> Result<(), Error> {
+        let mut content = Vec::new();
+        self.cookies
+            .lock()
+            .unwrap()
+            .save_incl_expired_and_nonpersistent(&mut content, serde_json::to_string)
+            .context(CookieStoreSnafu)?;
+        write_atomic(&self.path, &content).await
+    }
+}
+
+/// Whether a `meta=userinfo` response is for `user` rather than an anonymous session,
+/// any logged in user matches if `user` is [None]
+fn logged_in_as(response: &Value, user: Option<&str>) -> bool {
+    let info = &response["query"]["userinfo"];
+    !info["anon"].as_bool().unwrap_or(false)
+        && user.is_none_or(|user| info["name"].as_str() == Some(user))
+}
+
+/// Logs `client` in with the method configured in [Config::auth]
+async fn log_in(cfg: &Config, client: &Client) -> Result<(), Error> {
+    match cfg.auth {
+        WikiAuth::ClientLogin => client_login(cfg, client).await,
+        WikiAuth::BotPassword => bot_login(cfg, client).await,
+        // the token is sent with every request, there is nothing to log in with
+        WikiAuth::OAuth { .. } => whatever!("the wiki rejected the OAuth access token"),
+    }
+}
+
+async fn login_token(cfg: &Config, client: &Client) -> Result<String, Error> {
+    let mut form = HashMap::new();
+    form.insert("action", "query");
+    form.insert("meta", "tokens");
+    form.insert("type", "login");
+    form.insert("format", "json");
+    Ok(
+        retry::send(|| client.get(&cfg.endpoint).query(&form), &cfg.retry).await?["query"]
+            ["tokens"]["logintoken"]
+        .as_str()
+        .whatever_context("invalid response")?
+        .to_string())
+}
+
+/// Logs `client` in with `action=login` and a Special:BotPasswords grant
+async fn bot_login(cfg: &Config, client: &Client) -> Result<(), Error> {
+    let token = login_token(cfg, client).await?;
+    let mut form = HashMap::new();
+    form.insert("format", "json");
+    form.insert("action", "login");
+    form.insert("lgname", &cfg.login);
+    form.insert("lgpassword", &cfg.passwd);
+    form.insert("lgtoken", &token);
+    let login = retry::send(|| client.post(&cfg.endpoint).form(&form), &cfg.retry).await?;
+    ensure_whatever!(
+        login["login"]["result"].as_str() == Some("Success"),
+        "Login failed: {}",
+        &login
+    );
+    Ok(())
+}
+
+/// Logs `client` in with `action=clientlogin`
+async fn client_login(cfg: &Config, client: &Client) -> Result<(), Error> {
+    let login = login_token(cfg, client).await?;
+    let mut form = HashMap::new();
+    form.insert("format", "json");
+    form.insert("action", "clientlogin");
+    form.insert("loginreturnurl", &cfg.endpoint);
+    form.insert("logintoken", &login);
+    form.insert("username", &cfg.login);
+    form.insert("password", &cfg.passwd);
+    // persistent cookies let the next run skip the login
+    form.insert("rememberMe", "1");
+    let login = retry::send(|| client.post(&cfg.endpoint).form(&form), &cfg.retry).await?;
+    ensure_whatever!(
+        login["clientlogin"]["status"].as_str() == Some("PASS"),
+        "Login failed: {}",
+        &login
+    );
+    Ok(())
+}
+
+/// Fetches a CSRF token, failing if the wiki hands out the anonymous one
+async fn fetch_csrf(cfg: &Config, client: &Client) -> Result<String, Error> {
+    let mut form = HashMap::new();
+    form.insert("action", "query");
+    form.insert("meta", "tokens");
+    form.insert("format", "json");
+    let response = retry::send(|| client.get(&cfg.endpoint).query(&form), &cfg.retry).await?;
+    let token = response["query"]["tokens"]["csrftoken"]
+        .as_str()
+        .whatever_context("invalid response")?;
+    ensure_whatever!(token != r#"+\"#, "failed to get token: {}", &response);
+    Ok(token.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn userinfo() {
+        let anon = serde_json::json!({"query": {"userinfo": {"id": 0, "name": "127.0.0.1", "anon": true}}});
+        let user = serde_json::json!({"query": {"userinfo": {"id": 3, "name": "Archiver"}}});
+        assert!(!logged_in_as(&anon, Some("Archiver")));
+        assert!(!logged_in_as(&anon, None));
+        assert!(logged_in_as(&user, Some("Archiver")));
+        assert!(logged_in_as(&user, None));
+        assert!(!logged_in_as(&user, Some("Someone")));
+    }
+    #[tokio::test]
+    async fn cookies_roundtrip() {
+        let path = std::env::temp_dir().join("sd-archivemanager-cookies-test.json");
+        let _ = fs::remove_file(&path).await;
+        let session = Session::open_at(path.clone(), &WikiAuth::ClientLogin).await.unwrap();
+        let url = url::Url::parse("https://wiki.example/w/api.php").unwrap();
+        session
+            .cookies
+            .lock()
+            .unwrap()
+            .parse("session=abc; Path=/", &url)
+            .unwrap();
+        session.save().await.unwrap();
+        let session = Session::open_at(path.clone(), &WikiAuth::ClientLogin).await.unwrap();
+        assert!(
+            session
+                .cookies
+                .lock()
+                .unwrap()
+                .get("wiki.example", "/", "session")
+                .is_some()
+        );
+        fs::remove_file(&path).await.unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use tokio::fs;
+use xdg::BaseDirectories;
+
+use crate::{
+    error::{Error, IoSnafu, JSONSnafu, XdgSnafu},
+    regex::Target,
+};
+
+/// Writes `content` next to `path` first and renames it over `path`, so an interrupted
+/// write never leaves a truncated file behind. Missing parent directories are created.
+pub(crate) async fn write_atomic(path: &Path, content: &[u8]) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await.context(IoSnafu { file: dir })?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, content)
+        .await
+        .context(IoSnafu { file: &tmp })?;
+    fs::rename(&tmp, path).await.context(IoSnafu { file: path })?;
+    Ok(())
+}
+
+/// A scraped document as it went through the pipeline
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Record<T> {
+    pub guild: u64,
+    /// Discord message (or thread) ID the document was scraped from
+    pub id: u64,
+    pub target: Target,
+    /// Document as returned by the scraper
+    pub raw: T,
+    /// Document after regex profiles and plugins were applied
+    pub formatted: T,
+    /// Wikitext produced by the template
+    pub rendered: String,
+    pub scraped_at: DateTime<Utc>,
+}
+
+/// Local archive of scraped documents in `$XDG_DATA_HOME/sd-archivemanager/archive`,
+/// laid out as `<guild>/<target>/<message id>.json`
+#[derive(Debug, Clone)]
+pub struct Store {
+    root: PathBuf,
+}
+
+impl Store {
+    pub fn open() -> Result<Self, Error> {
+        let xdg = BaseDirectories::with_prefix("sd-archivemanager").context(XdgSnafu)?;
+        Ok(Store {
+            root: xdg.get_data_home().join("archive"),
+        })
+    }
+    /// Uses `root` instead of the XDG data directory
+    pub fn with_root(root: PathBuf) -> Self {
+        Store { root }
+    }
+    fn dir(&self, guild: u64, target: Target) -> PathBuf {
+        self.root.join(guild.to_string()).join(target.name())
+    }
+    fn path(&self, guild: u64, target: Target, id: u64) -> PathBuf {
+        self.dir(guild, target).join(format!("{id}.json"))
+    }
+    /// Writes a record with [write_atomic], replacing an older record of the same message
+    pub async fn save<T: Serialize>(&self, record: &Record<T>) -> Result<(), Error> {
+        let path = self.path(record.guild, record.target, record.id);
+        let content = serde_json::to_vec_pretty(record).context(JSONSnafu)?;
+        write_atomic(&path, &content).await
+    }
+    /// Loads the record of a message, [None] if it was never archived
+    pub async fn load<T: for<'de> Deserialize<'de>>(
+        &self,
+        guild: u64,
+        target: Target,
+        id: u64,
+    ) -> Result<Option<Record<T>>, Error> {
+        let path = self.path(guild, target, id);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read(&path).await.context(IoSnafu { file: &path })?;
+        Ok(Some(serde_json::from_slice(&content).context(JSONSnafu)?))
+    }
+    /// Lists the IDs of every archived message of a target in a guild, oldest first
+    pub async fn list(&self, guild: u64, target: Target) -> Result<Vec<u64>, Error> {
+        let dir = self.dir(guild, target);
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut entries = fs::read_dir(&dir).await.context(IoSnafu { file: &dir })?;
+        let mut ids = Vec::new();
+        while let Some(entry) = entries.next_entry().await.context(IoSnafu { file: &dir })? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(id) = path.file_stem().and_then(|s| s.to_str()?.parse().ok()) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn save_load_list() {
+        let root = std::env::temp_dir().join("sd-archivemanager-store-test");
+        let _ = fs::remove_dir_all(&root).await;
+        let store = Store::with_root(root.clone());
+        let record = Record {
+            guild: 1,
+            id: 42,
+            target: Target::EO,
+            raw: "raw".to_string(),
+            formatted: "formatted".to_string(),
+            rendered: "rendered".to_string(),
+            scraped_at: Utc::now(),
+        };
+        store.save(&record).await.unwrap();
+        store.save(&Record { id: 7, ..record.clone() }).await.unwrap();
+        assert_eq!(store.load(1, Target::EO, 42).await.unwrap(), Some(record));
+        assert_eq!(store.load::<String>(1, Target::EO, 1).await.unwrap(), None);
+        assert_eq!(store.list(1, Target::EO).await.unwrap(), vec![7, 42]);
+        assert!(store.list(1, Target::Legislation).await.unwrap().is_empty());
+        fs::remove_dir_all(&root).await.unwrap();
+    }
+}